@@ -0,0 +1,250 @@
+use ::comrak::nodes::{AlertType, AstNode, ListType, NodeValue, TableAlignment};
+use ::comrak::{Arena, Options, parse_document};
+use markdown::mdast::{
+    AlignKind, Blockquote, Break, Code, Delete, Emphasis, FootnoteDefinition, FootnoteReference,
+    Heading, Html, Image, InlineCode, InlineMath, Link, List, ListItem, Math, Node, Paragraph,
+    Root, Strong, Table, TableCell, TableRow, Text, ThematicBreak, Toml, Yaml,
+};
+use markdown::unist::Position;
+
+use super::ParserBackend;
+use super::convert::{LineIndex, paragraph, split_info, wrap_in_html, wrap_inline_runs};
+
+/// Parses markdown with `comrak`.
+#[derive(Debug, Clone)]
+pub struct ComrakBackend {
+    pub options: Options<'static>,
+}
+
+impl Default for ComrakBackend {
+    /// Enables every extension that can be represented in the mdast.
+    fn default() -> Self {
+        let mut options = Options::default();
+        let extension = &mut options.extension;
+        extension.strikethrough = true;
+        extension.table = true;
+        extension.autolink = true;
+        extension.tasklist = true;
+        extension.superscript = true;
+        extension.subscript = true;
+        extension.underline = true;
+        extension.highlight = true;
+        extension.insert = true;
+        extension.footnotes = true;
+        extension.description_lists = true;
+        extension.front_matter_delimiter = Some("---".to_owned());
+        extension.multiline_block_quotes = true;
+        extension.alerts = true;
+        extension.math_dollars = true;
+        extension.wikilinks_title_after_pipe = true;
+
+        Self { options }
+    }
+}
+
+impl ParserBackend for ComrakBackend {
+    fn parse(&self, markdown: &str) -> Result<Node, String> {
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown, &self.options);
+        let index = LineIndex::new(markdown);
+
+        convert(root, &index).into_iter().next()
+            .ok_or_else(|| "comrak returned no document".to_owned())
+    }
+}
+
+/// Gets the position of a comrak node, whose end column is inclusive.
+fn position(node: &AstNode, index: &LineIndex) -> Option<Position> {
+    let sourcepos = node.data().sourcepos;
+    if sourcepos.start.line == 0 {
+        return None;
+    }
+
+    let start = index.offset(sourcepos.start.line, sourcepos.start.column);
+    let end = index.offset(sourcepos.end.line, sourcepos.end.column + 1);
+    Some(index.position(start..end.max(start)))
+}
+
+/// Strips the delimiter lines from front matter.
+fn front_matter(raw: &str, position: Option<Position>) -> Node {
+    let mut lines = raw.trim().lines();
+    let delimiter = lines.next().unwrap_or_default();
+    let mut value = lines.collect::<Vec<_>>();
+    if value.last() == Some(&delimiter) {
+        value.pop();
+    }
+
+    let value = value.join("\n");
+    if delimiter == "+++" {
+        Node::Toml(Toml { value, position })
+    } else {
+        Node::Yaml(Yaml { value, position })
+    }
+}
+
+/// Puts back the `[!KIND]` marker comrak parses alerts from, since the
+/// renderer detects alerts by the marker, like the `markdown` crate leaves it.
+fn with_alert_marker(mut children: Vec<Node>, alert_type: AlertType, position: Option<Position>) -> Vec<Node> {
+    let marker = format!("[!{}]", alert_type.default_title().to_uppercase());
+    let text = |value| Node::Text(Text {
+        value,
+        position: position.clone(),
+    });
+
+    // The marker is on its own line, before the alert's first paragraph.
+    match children.first_mut() {
+        Some(Node::Paragraph(first)) => first.children.insert(0, text(marker + "\n")),
+        _ => children.insert(0, paragraph(vec![text(marker)], position.clone())),
+    }
+    children
+}
+
+/// Converts a comrak node and its descendants, returning more than one node
+/// when the node is flattened into its parent.
+fn convert<'a>(node: &'a AstNode<'a>, index: &LineIndex) -> Vec<Node> {
+    let position = position(node, index);
+    let value = node.data().value.clone();
+    let children = || node.children()
+        .flat_map(|child| convert(child, index))
+        .collect::<Vec<_>>();
+
+    let converted = match value {
+        NodeValue::Document => Node::Root(Root { children: children(), position }),
+        NodeValue::FrontMatter(raw) => front_matter(&raw, position),
+        NodeValue::BlockQuote | NodeValue::MultilineBlockQuote(_) => {
+            Node::Blockquote(Blockquote { children: children(), position })
+        },
+        NodeValue::Alert(alert) => Node::Blockquote(Blockquote {
+            children: with_alert_marker(children(), alert.alert_type, position.clone()),
+            position,
+        }),
+        NodeValue::List(list) => Node::List(List {
+            children: children(),
+            position,
+            ordered: list.list_type == ListType::Ordered,
+            start: (list.list_type == ListType::Ordered).then_some(list.start as u32),
+            spread: !list.tight,
+        }),
+        NodeValue::Item(_) | NodeValue::DescriptionDetails => Node::ListItem(ListItem {
+            children: wrap_inline_runs(children()),
+            position,
+            spread: false,
+            checked: None,
+        }),
+        NodeValue::TaskItem(task) => Node::ListItem(ListItem {
+            children: wrap_inline_runs(children()),
+            position,
+            spread: false,
+            checked: Some(task.symbol.is_some()),
+        }),
+        // Terms are kept as bold paragraphs inside a bullet list of their definitions.
+        NodeValue::DescriptionList => Node::List(List {
+            children: children(),
+            position,
+            ordered: false,
+            start: None,
+            spread: false,
+        }),
+        NodeValue::DescriptionTerm => {
+            return children().into_iter()
+                .map(|child| match child {
+                    Node::Paragraph(paragraph) => Node::Paragraph(Paragraph {
+                        children: vec![Node::Strong(Strong {
+                            children: paragraph.children,
+                            position: paragraph.position.clone(),
+                        })],
+                        position: paragraph.position,
+                    }),
+                    child => child,
+                })
+                .collect();
+        },
+        NodeValue::CodeBlock(code) => {
+            let (lang, meta) = split_info(&code.info);
+            Node::Code(Code {
+                value: code.literal.trim_end_matches('\n').to_owned(),
+                position,
+                lang,
+                meta,
+            })
+        },
+        NodeValue::HtmlBlock(html) => Node::Html(Html {
+            value: html.literal.trim_end_matches('\n').to_owned(),
+            position,
+        }),
+        NodeValue::HtmlInline(value) | NodeValue::Raw(value) => Node::Html(Html { value, position }),
+        NodeValue::Paragraph => paragraph(children(), position),
+        NodeValue::Heading(heading) => Node::Heading(Heading {
+            children: children(),
+            position,
+            depth: heading.level,
+        }),
+        NodeValue::ThematicBreak => Node::ThematicBreak(ThematicBreak { position }),
+        NodeValue::FootnoteDefinition(footnote) => Node::FootnoteDefinition(FootnoteDefinition {
+            children: children(),
+            position,
+            identifier: footnote.name.to_lowercase(),
+            label: Some(footnote.name),
+        }),
+        NodeValue::FootnoteReference(footnote) => Node::FootnoteReference(FootnoteReference {
+            position,
+            identifier: footnote.name.to_lowercase(),
+            label: Some(footnote.name),
+        }),
+        NodeValue::Table(table) => Node::Table(Table {
+            children: children(),
+            position,
+            align: table.alignments.iter()
+                .map(|alignment| match alignment {
+                    TableAlignment::None => AlignKind::None,
+                    TableAlignment::Left => AlignKind::Left,
+                    TableAlignment::Center => AlignKind::Center,
+                    TableAlignment::Right => AlignKind::Right,
+                })
+                .collect(),
+        }),
+        NodeValue::TableRow(_) => Node::TableRow(TableRow { children: children(), position }),
+        NodeValue::TableCell => Node::TableCell(TableCell { children: children(), position }),
+        NodeValue::Text(text) => Node::Text(Text { value: text.into_owned(), position }),
+        NodeValue::EscapedTag(tag) => Node::Text(Text { value: tag.to_owned(), position }),
+        NodeValue::SoftBreak => Node::Text(Text { value: "\n".to_owned(), position }),
+        NodeValue::LineBreak => Node::Break(Break { position }),
+        NodeValue::Code(code) => Node::InlineCode(InlineCode { value: code.literal, position }),
+        NodeValue::Emph => Node::Emphasis(Emphasis { children: children(), position }),
+        NodeValue::Strong => Node::Strong(Strong { children: children(), position }),
+        NodeValue::Strikethrough => Node::Delete(Delete { children: children(), position }),
+        NodeValue::Superscript => return wrap_in_html("sup", children(), position.as_ref()),
+        NodeValue::Subscript => return wrap_in_html("sub", children(), position.as_ref()),
+        NodeValue::Underline => return wrap_in_html("u", children(), position.as_ref()),
+        NodeValue::Insert => return wrap_in_html("ins", children(), position.as_ref()),
+        NodeValue::Highlight => return wrap_in_html("mark", children(), position.as_ref()),
+        NodeValue::Link(link) => Node::Link(Link {
+            children: children(),
+            position,
+            url: link.url,
+            title: (!link.title.is_empty()).then_some(link.title),
+        }),
+        NodeValue::WikiLink(link) => Node::Link(Link {
+            children: children(),
+            position,
+            url: link.url,
+            title: None,
+        }),
+        NodeValue::Image(link) => Node::Image(Image {
+            position,
+            alt: children().iter().map(ToString::to_string).collect(),
+            url: link.url,
+            title: (!link.title.is_empty()).then_some(link.title),
+        }),
+        NodeValue::Math(math) if math.display_math => Node::Math(Math {
+            value: math.literal,
+            position,
+            meta: None,
+        }),
+        NodeValue::Math(math) => Node::InlineMath(InlineMath { value: math.literal, position }),
+        // Anything else, such as spoilers and directives, keeps only its content.
+        _ => return children(),
+    };
+
+    vec![converted]
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+use markdown::mdast::{InlineMath, Node, Paragraph};
+use markdown::unist::{Point, Position};
+
+/// Converts byte offsets and line/column pairs in a source to mdast positions.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            line_starts,
+            len: source.len(),
+        }
+    }
+
+    /// Gets the point at a byte offset.
+    pub fn point(&self, offset: usize) -> Point {
+        let offset = offset.min(self.len);
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        Point::new(line, offset - self.line_starts[line - 1] + 1, offset)
+    }
+
+    /// Gets the position spanning a byte range.
+    pub fn position(&self, range: Range<usize>) -> Position {
+        Position {
+            start: self.point(range.start),
+            end: self.point(range.end),
+        }
+    }
+
+    /// Gets the byte offset of a 1-based line and byte column.
+    #[cfg(feature = "comrak")]
+    pub fn offset(&self, line: usize, column: usize) -> usize {
+        self.line_starts.get(line.saturating_sub(1))
+            .map_or(self.len, |start| start + column.saturating_sub(1))
+            .min(self.len)
+    }
+}
+
+/// Wraps inline nodes in an HTML tag from the supported inline subset.
+#[cfg(feature = "comrak")]
+pub fn wrap_in_html(tag: &str, children: Vec<Node>, position: Option<&Position>) -> Vec<Node> {
+    let html = |value: String| Node::Html(markdown::mdast::Html {
+        value,
+        position: position.cloned(),
+    });
+
+    let mut nodes = vec![html(format!("<{}>", tag))];
+    nodes.extend(children);
+    nodes.push(html(format!("</{}>", tag)));
+    nodes
+}
+
+/// Splits a fence info string into the language and the rest of the meta.
+pub fn split_info(info: &str) -> (Option<String>, Option<String>) {
+    let info = info.trim();
+    let (lang, meta) = info.split_once(char::is_whitespace).unwrap_or((info, ""));
+    let meta = meta.trim();
+
+    (
+        (!lang.is_empty()).then(|| lang.to_owned()),
+        (!meta.is_empty()).then(|| meta.to_owned()),
+    )
+}
+
+/// Creates a paragraph, turning display math that makes up the whole
+/// paragraph into a math block and any other display math into inline math.
+pub fn paragraph(mut children: Vec<Node>, position: Option<Position>) -> Node {
+    if matches!(children.as_slice(), [Node::Math(_)]) {
+        return children.remove(0);
+    }
+
+    for child in &mut children {
+        if let Node::Math(math) = child {
+            *child = Node::InlineMath(InlineMath {
+                value: std::mem::take(&mut math.value),
+                position: math.position.take(),
+            });
+        }
+    }
+
+    Node::Paragraph(Paragraph { children, position })
+}
+
+/// Wraps each run of inline nodes in a paragraph, e.g. for the content of
+/// tight list items, since list items are expected to contain blocks.
+pub fn wrap_inline_runs(children: Vec<Node>) -> Vec<Node> {
+    let mut wrapped = Vec::new();
+    let mut run = Vec::new();
+
+    for child in children {
+        if is_block(&child) {
+            if !run.is_empty() {
+                wrapped.push(inline_run(std::mem::take(&mut run)));
+            }
+            wrapped.push(child);
+        } else {
+            run.push(child);
+        }
+    }
+
+    if !run.is_empty() {
+        wrapped.push(inline_run(run));
+    }
+    wrapped
+}
+
+fn inline_run(children: Vec<Node>) -> Node {
+    let position = match (children.first().and_then(Node::position), children.last().and_then(Node::position)) {
+        (Some(start), Some(end)) => Some(Position {
+            start: start.start.clone(),
+            end: end.end.clone(),
+        }),
+        _ => None,
+    };
+
+    paragraph(children, position)
+}
+
+fn is_block(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Paragraph(_) | Node::Heading(_) | Node::Code(_) | Node::List(_) | Node::Blockquote(_)
+            | Node::Table(_) | Node::ThematicBreak(_) | Node::Math(_) | Node::FootnoteDefinition(_)
+    )
+}
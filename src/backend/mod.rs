@@ -0,0 +1,65 @@
+#[cfg(feature = "comrak")]
+mod comrak;
+#[cfg(any(feature = "comrak", feature = "pulldown-cmark"))]
+mod convert;
+#[cfg(feature = "pulldown-cmark")]
+mod pulldown;
+
+use markdown::mdast::Node;
+
+#[cfg(feature = "comrak")]
+pub use comrak::ComrakBackend;
+#[cfg(feature = "pulldown-cmark")]
+pub use pulldown::PulldownCmarkBackend;
+
+/// Parses markdown into the mdast tree the renderer consumes.
+///
+/// The `markdown` crate is always used unless another backend is set with
+/// `MarkdownView::set_parser_backend`. Other parsers are converted into the
+/// same mdast shape, with extensions that have no mdast node mapped to the
+/// closest node the renderer understands (e.g. superscript to `<sup>` HTML).
+pub trait ParserBackend {
+    /// Parses the markdown, returning a `Node::Root`.
+    fn parse(&self, markdown: &str) -> Result<Node, String>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the first line of the text node starting each top-level
+    /// blockquote, uppercased, which is where alerts are detected from.
+    fn quote_first_lines(root: &Node) -> Vec<String> {
+        root.children().into_iter().flatten()
+            .filter_map(|node| match node {
+                Node::Blockquote(quote) => quote.children.first(),
+                _ => None,
+            })
+            .map(|first| match first.children().and_then(|children| children.first()) {
+                Some(Node::Text(text)) => text.value.lines().next().unwrap_or_default().to_uppercase(),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backends_agree_on_alerts() {
+        let markdown = "> [!NOTE]\n> Useful.\n\n> [!warning]\n> - Careful.\n\n> Just a quote.";
+        let expected = ["[!NOTE]", "[!WARNING]", "JUST A QUOTE."];
+
+        let root = markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()).expect("GFM always parses");
+        assert_eq!(quote_first_lines(&root), expected);
+
+        #[cfg(feature = "comrak")]
+        {
+            let root = ComrakBackend::default().parse(markdown).expect("comrak always parses");
+            assert_eq!(quote_first_lines(&root), expected);
+        }
+
+        #[cfg(feature = "pulldown-cmark")]
+        {
+            let root = PulldownCmarkBackend::default().parse(markdown).expect("pulldown-cmark always parses");
+            assert_eq!(quote_first_lines(&root), expected);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use markdown::mdast::{
+    AlignKind, Blockquote, Break, Code, Delete, Emphasis, FootnoteDefinition, FootnoteReference,
+    Heading, Html, Image, InlineCode, InlineMath, Link, List, ListItem, Math, Node, Paragraph,
+    Root, Strong, Table, TableCell, TableRow, Text, ThematicBreak, Toml, Yaml,
+};
+use markdown::unist::Position;
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, MetadataBlockKind, Options, Parser, Tag, TagEnd};
+
+use super::ParserBackend;
+use super::convert::{LineIndex, paragraph, split_info, wrap_inline_runs};
+
+/// Parses markdown with `pulldown-cmark`.
+#[derive(Debug, Clone)]
+pub struct PulldownCmarkBackend {
+    pub options: Options,
+}
+
+impl Default for PulldownCmarkBackend {
+    /// Enables every extension that can be represented in the mdast.
+    fn default() -> Self {
+        Self {
+            options: Options::ENABLE_TABLES
+                | Options::ENABLE_FOOTNOTES
+                | Options::ENABLE_STRIKETHROUGH
+                | Options::ENABLE_TASKLISTS
+                | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+                | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+                | Options::ENABLE_MATH
+                | Options::ENABLE_DEFINITION_LIST
+                | Options::ENABLE_SUPERSCRIPT
+                | Options::ENABLE_SUBSCRIPT
+                | Options::ENABLE_WIKILINKS,
+        }
+    }
+}
+
+impl ParserBackend for PulldownCmarkBackend {
+    fn parse(&self, markdown: &str) -> Result<Node, String> {
+        let index = LineIndex::new(markdown);
+        let mut converter = Converter {
+            stack: vec![Frame::Node(Node::Root(Root {
+                children: Vec::new(),
+                position: Some(index.position(0..markdown.len())),
+            }))],
+        };
+
+        for (event, range) in Parser::new_ext(markdown, self.options).into_offset_iter() {
+            converter.event(event, Some(index.position(range)));
+        }
+
+        match converter.stack.into_iter().next() {
+            Some(Frame::Node(root)) => Ok(root),
+            _ => Err("unbalanced pulldown-cmark events".to_owned()),
+        }
+    }
+}
+
+/// A tag that is open while converting events.
+enum Frame {
+    Node(Node),
+    /// Inline content wrapped in an HTML tag, whose children are added to
+    /// the enclosing node directly.
+    Html(&'static str),
+    /// An image, whose text content becomes its alt text.
+    Image(Image),
+}
+
+/// Builds an mdast tree from a stream of `pulldown-cmark` events.
+struct Converter {
+    stack: Vec<Frame>,
+}
+
+impl Converter {
+    fn event(&mut self, event: Event, position: Option<Position>) {
+        match event {
+            Event::Start(tag) => self.start(tag, position),
+            Event::End(end) => self.end(end, position),
+            Event::Text(text) => self.push_text(&text, position),
+            Event::Code(code) => self.push(Node::InlineCode(InlineCode {
+                value: code.into_string(),
+                position,
+            })),
+            Event::InlineMath(math) => self.push(Node::InlineMath(InlineMath {
+                value: math.into_string(),
+                position,
+            })),
+            Event::DisplayMath(math) => self.push(Node::Math(Math {
+                value: math.into_string(),
+                position,
+                meta: None,
+            })),
+            Event::Html(html) | Event::InlineHtml(html) => {
+                if let Some(Frame::Node(Node::Html(block))) = self.stack.last_mut() {
+                    block.value.push_str(&html);
+                } else {
+                    self.push(Node::Html(Html {
+                        value: html.into_string(),
+                        position,
+                    }));
+                }
+            },
+            Event::FootnoteReference(label) => self.push(Node::FootnoteReference(FootnoteReference {
+                position,
+                identifier: label.to_lowercase(),
+                label: Some(label.into_string()),
+            })),
+            Event::SoftBreak => self.push_text("\n", position),
+            Event::HardBreak => self.push(Node::Break(Break { position })),
+            Event::Rule => self.push(Node::ThematicBreak(ThematicBreak { position })),
+            Event::TaskListMarker(checked) => {
+                let item = self.stack.iter_mut().rev().find_map(|frame| match frame {
+                    Frame::Node(Node::ListItem(item)) => Some(item),
+                    _ => None,
+                });
+
+                if let Some(item) = item {
+                    item.checked = Some(checked);
+                }
+            },
+        }
+    }
+
+    fn start(&mut self, tag: Tag, position: Option<Position>) {
+        let children = Vec::new();
+        let node = match tag {
+            Tag::Paragraph | Tag::DefinitionListTitle => Node::Paragraph(Paragraph { children, position }),
+            Tag::Heading { level, .. } => Node::Heading(Heading {
+                children,
+                position,
+                depth: level as u8,
+            }),
+            Tag::BlockQuote(_) => Node::Blockquote(Blockquote { children, position }),
+            Tag::CodeBlock(kind) => {
+                let (lang, meta) = match kind {
+                    CodeBlockKind::Fenced(info) => split_info(&info),
+                    CodeBlockKind::Indented => (None, None),
+                };
+                Node::Code(Code {
+                    value: String::new(),
+                    position,
+                    lang,
+                    meta,
+                })
+            },
+            Tag::HtmlBlock => Node::Html(Html {
+                value: String::new(),
+                position,
+            }),
+            Tag::List(start) => Node::List(List {
+                children,
+                position,
+                ordered: start.is_some(),
+                start: start.and_then(|start| u32::try_from(start).ok()),
+                spread: false,
+            }),
+            // Terms are kept as bold paragraphs inside a bullet list of their definitions.
+            Tag::DefinitionList => Node::List(List {
+                children,
+                position,
+                ordered: false,
+                start: None,
+                spread: false,
+            }),
+            Tag::Item | Tag::DefinitionListDefinition => Node::ListItem(ListItem {
+                children,
+                position,
+                spread: false,
+                checked: None,
+            }),
+            Tag::FootnoteDefinition(label) => Node::FootnoteDefinition(FootnoteDefinition {
+                children,
+                position,
+                identifier: label.to_lowercase(),
+                label: Some(label.into_string()),
+            }),
+            Tag::Table(alignments) => Node::Table(Table {
+                children,
+                position,
+                align: alignments.into_iter()
+                    .map(|alignment| match alignment {
+                        Alignment::None => AlignKind::None,
+                        Alignment::Left => AlignKind::Left,
+                        Alignment::Center => AlignKind::Center,
+                        Alignment::Right => AlignKind::Right,
+                    })
+                    .collect(),
+            }),
+            Tag::TableHead | Tag::TableRow => Node::TableRow(TableRow { children, position }),
+            Tag::TableCell => Node::TableCell(TableCell { children, position }),
+            Tag::Emphasis => Node::Emphasis(Emphasis { children, position }),
+            Tag::Strong => Node::Strong(Strong { children, position }),
+            Tag::Strikethrough => Node::Delete(Delete { children, position }),
+            Tag::Superscript | Tag::Subscript => {
+                let name = if matches!(tag, Tag::Superscript) { "sup" } else { "sub" };
+                self.push(Node::Html(Html {
+                    value: format!("<{}>", name),
+                    position,
+                }));
+                self.stack.push(Frame::Html(name));
+                return;
+            },
+            Tag::Link { dest_url, title, .. } => Node::Link(Link {
+                children,
+                position,
+                url: dest_url.into_string(),
+                title: (!title.is_empty()).then(|| title.into_string()),
+            }),
+            Tag::Image { dest_url, title, .. } => {
+                self.stack.push(Frame::Image(Image {
+                    position,
+                    alt: String::new(),
+                    url: dest_url.into_string(),
+                    title: (!title.is_empty()).then(|| title.into_string()),
+                }));
+                return;
+            },
+            Tag::MetadataBlock(MetadataBlockKind::YamlStyle) => Node::Yaml(Yaml {
+                value: String::new(),
+                position,
+            }),
+            Tag::MetadataBlock(MetadataBlockKind::PlusesStyle) => Node::Toml(Toml {
+                value: String::new(),
+                position,
+            }),
+        };
+
+        self.stack.push(Frame::Node(node));
+    }
+
+    fn end(&mut self, end: TagEnd, position: Option<Position>) {
+        let node = match self.stack.pop() {
+            Some(Frame::Node(node)) => node,
+            Some(Frame::Html(name)) => Node::Html(Html {
+                value: format!("</{}>", name),
+                position,
+            }),
+            Some(Frame::Image(image)) => Node::Image(image),
+            None => return,
+        };
+
+        let node = match (end, node) {
+            (TagEnd::DefinitionListTitle, Node::Paragraph(paragraph)) => Node::Paragraph(Paragraph {
+                children: vec![Node::Strong(Strong {
+                    children: paragraph.children,
+                    position: paragraph.position.clone(),
+                })],
+                position: paragraph.position,
+            }),
+            // Tight list items and definitions contain inline content directly.
+            (TagEnd::Item | TagEnd::DefinitionListDefinition, Node::ListItem(mut item)) => {
+                item.children = wrap_inline_runs(std::mem::take(&mut item.children));
+                Node::ListItem(item)
+            },
+            (TagEnd::Paragraph, Node::Paragraph(node)) => paragraph(node.children, node.position),
+            (TagEnd::CodeBlock, Node::Code(mut code)) => {
+                code.value.truncate(code.value.trim_end_matches('\n').len());
+                Node::Code(code)
+            },
+            (TagEnd::MetadataBlock(_), Node::Yaml(mut yaml)) => {
+                yaml.value.truncate(yaml.value.trim_end_matches('\n').len());
+                Node::Yaml(yaml)
+            },
+            (TagEnd::MetadataBlock(_), Node::Toml(mut toml)) => {
+                toml.value.truncate(toml.value.trim_end_matches('\n').len());
+                Node::Toml(toml)
+            },
+            (TagEnd::HtmlBlock, Node::Html(mut html)) => {
+                html.value.truncate(html.value.trim_end_matches('\n').len());
+                Node::Html(html)
+            },
+            (_, node) => node,
+        };
+
+        self.push(node);
+    }
+
+    /// Adds a node to the innermost open node, or to an open image's alt text.
+    fn push(&mut self, node: Node) {
+        for frame in self.stack.iter_mut().rev() {
+            match frame {
+                Frame::Html(..) => continue,
+                Frame::Image(image) => image.alt.push_str(&node.to_string()),
+                Frame::Node(parent) => if let Some(children) = parent.children_mut() {
+                    children.push(node);
+                },
+            }
+            return;
+        }
+    }
+
+    fn push_text(&mut self, text: &str, position: Option<Position>) {
+        match self.stack.last_mut() {
+            Some(Frame::Node(Node::Code(Code { value, .. })))
+                | Some(Frame::Node(Node::Yaml(Yaml { value, .. })))
+                | Some(Frame::Node(Node::Toml(Toml { value, .. })))
+                | Some(Frame::Node(Node::Html(Html { value, .. }))) => value.push_str(text),
+            Some(Frame::Image(image)) => image.alt.push_str(text),
+            _ => if let Some(Node::Text(last)) = self.last_child_mut() {
+                // pulldown-cmark splits text at brackets and the like, which
+                // is joined back up like the `markdown` crate does, e.g. so an
+                // alert's `[!NOTE]` marker is a single node.
+                last.value.push_str(text);
+                if let (Some(last), Some(position)) = (&mut last.position, position) {
+                    last.end = position.end;
+                }
+            } else {
+                self.push(Node::Text(Text {
+                    value: text.to_owned(),
+                    position,
+                }));
+            },
+        }
+    }
+
+    /// Gets the last child of the node content is currently pushed to.
+    fn last_child_mut(&mut self) -> Option<&mut Node> {
+        match self.stack.iter_mut().rev().find(|frame| !matches!(frame, Frame::Html(..)))? {
+            Frame::Node(parent) => parent.children_mut()?.last_mut(),
+            Frame::Image(_) | Frame::Html(..) => None,
+        }
+    }
+}
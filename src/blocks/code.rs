@@ -1,23 +1,70 @@
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
 use futures_signals::signal::Mutable;
 use gtk4::prelude::*;
 use gtk4::TextBuffer;
-use sourceview5::{View, Buffer, LanguageManager, StyleSchemeManager, BackgroundPatternType};
+#[cfg(feature = "sourceview")]
+use gtk4::glib;
+#[cfg(feature = "sourceview")]
+use sourceview5::{View, Buffer, BackgroundPatternType, GutterRendererText, SearchContext, SearchSettings};
+#[cfg(feature = "sourceview")]
 use sourceview5::prelude::*;
 use markdown::mdast::Node;
 
+use crate::find::FindOptions;
+#[cfg(any(feature = "sourceview", feature = "syntect"))]
+use crate::highlight;
+use crate::highlight::{CodeTheme, HighlightBackend};
+use crate::selection;
 use super::{BlockWidget, BlockWidgetFactory};
+use super::fence::FenceMeta;
+
+/// Without GtkSourceView, code is shown in a plain monospace `TextView`.
+#[cfg(not(feature = "sourceview"))]
+type View = gtk4::TextView;
+
+/// The tag highlighting find matches when there's no GtkSourceView search.
+#[cfg(not(feature = "sourceview"))]
+const MATCH_TAG: &str = "cmark-codeblock-match";
+#[cfg(not(feature = "sourceview"))]
+const MATCH_BACKGROUND: &str = "#f6d32d";
+
+/// The tag marking lines highlighted by the fence's meta, e.g. ```` ```rust {3,5-7} ````.
+const LINE_HIGHLIGHT_TAG: &str = "cmark-codeblock-line-highlight";
+const LINE_HIGHLIGHT_BACKGROUND: &str = "rgba(53, 132, 228, 0.2)";
+#[cfg(feature = "sourceview")]
+const LINE_NUMBER_PADDING: i32 = 4;
+
+/// An active search in a code block, with the char offsets of every match.
+#[derive(Debug)]
+struct CodeSearch {
+    #[cfg(feature = "sourceview")]
+    context: SearchContext,
+    matches: Vec<(i32, i32)>,
+}
 
 #[derive(Debug, Clone)]
 pub struct CodeBlock {
     source_view: View,
-    line_cache: Rc<RefCell<HashMap<usize, (String, String)>>>,
+    #[cfg(feature = "syntect")]
+    line_cache: Rc<RefCell<highlight::LineCache>>,
+    search: Rc<RefCell<Option<CodeSearch>>>,
+    backend: Rc<Cell<HighlightBackend>>,
+    theme: Rc<Cell<CodeTheme>>,
+    meta: Rc<RefCell<FenceMeta>>,
+    /// Line numbers starting from the fence's `start`, since GtkSourceView's
+    /// own line numbers always start from 1.
+    #[cfg(feature = "sourceview")]
+    line_numbers: GutterRendererText,
+    #[cfg(feature = "sourceview")]
+    first_line: Rc<Cell<usize>>,
     /// The container widget for the code block.
     pub container: gtk4::Box,
     /// The `ScrolledWindow` containing the source view.
     pub root: gtk4::ScrolledWindow,
+    /// The filename header from the fence's `title`, hidden when there's none.
+    pub title: gtk4::Label,
     /// The language of the code block.
     pub lang: Mutable<Option<String>>,
 }
@@ -29,8 +76,16 @@ impl BlockWidget for CodeBlock {
 
     fn update(&mut self, node: &Node) {
         if let Node::Code(code) = node {
-            self.set_lang(code.lang.as_ref());
-            self.set_markup(&code.value);
+            let (lang, meta) = FenceMeta::parse(code.lang.as_deref(), code.meta.as_deref());
+            let lang_changed = self.set_lang(lang.as_ref());
+            let changed_line = self.set_markup(&code.value);
+            if lang_changed {
+                self.refresh_syntect(0);
+            } else if let Some(line) = changed_line {
+                self.refresh_syntect(line);
+            }
+
+            self.set_meta(meta, changed_line.is_some());
         }
     }
 
@@ -38,12 +93,81 @@ impl BlockWidget for CodeBlock {
         matches!(node, Node::Code(_))
     }
 
+    fn find(&self, query: &str, options: &FindOptions) -> usize {
+        let search = self.search(query, options);
+        let count = search.matches.len();
+        self.search.replace(Some(search));
+        count
+    }
+
+    fn select_match(&self, index: usize) -> Option<gtk4::Widget> {
+        let (start, end) = *self.search.borrow().as_ref()?.matches.get(index)?;
+        let buffer = self.buffer();
+        let mut start = buffer.iter_at_offset(start);
+        let end = buffer.iter_at_offset(end);
+
+        buffer.select_range(&start, &end);
+        self.source_view.scroll_to_iter(&mut start, 0.0, false, 0.0, 0.0);
+        Some(self.container.clone().upcast())
+    }
+
+    fn clear_find(&self) {
+        if let Some(search) = self.search.take() {
+            self.unhighlight(search);
+            let buffer = self.buffer();
+            buffer.place_cursor(&buffer.start_iter());
+        }
+    }
+
+    fn text(&self) -> String {
+        let buffer = self.buffer();
+        buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).into()
+    }
+
+    fn select(&self, range: Option<Range<usize>>) {
+        let buffer = self.buffer();
+        let Some(range) = range.filter(|range| !range.is_empty()) else {
+            buffer.place_cursor(&buffer.start_iter());
+            return;
+        };
+
+        let text = self.text();
+        let start = selection::byte_to_char_offset(&text, range.start);
+        let end = selection::byte_to_char_offset(&text, range.end);
+        buffer.select_range(&buffer.iter_at_offset(start as i32), &buffer.iter_at_offset(end as i32));
+    }
+
+    fn offset_at(&self, x: f64, y: f64) -> Option<usize> {
+        let point = gtk4::graphene::Point::new(x as f32, y as f32);
+        let local = self.container.compute_point(&self.source_view, &point)?;
+        let (x, y) = self.source_view.window_to_buffer_coords(
+            gtk4::TextWindowType::Widget,
+            local.x() as i32,
+            local.y() as i32,
+        );
+
+        let iter = self.source_view.iter_at_location(x, y)
+            .unwrap_or_else(|| if y < 0 { self.buffer().start_iter() } else { self.buffer().end_iter() });
+
+        Some(selection::char_to_byte_offset(&self.text(), iter.offset() as usize))
+    }
+
     fn clone(&self) -> Box<dyn BlockWidget> {
         Box::new(Self {
             source_view: self.source_view.clone(),
-            line_cache: Rc::new(RefCell::new(HashMap::new())),
+            #[cfg(feature = "syntect")]
+            line_cache: self.line_cache.clone(),
+            search: self.search.clone(),
+            backend: self.backend.clone(),
+            theme: self.theme.clone(),
+            meta: self.meta.clone(),
+            #[cfg(feature = "sourceview")]
+            line_numbers: self.line_numbers.clone(),
+            #[cfg(feature = "sourceview")]
+            first_line: self.first_line.clone(),
             container: self.container.clone(),
             root: self.root.clone(),
+            title: self.title.clone(),
             lang: self.lang.clone(),
         })
     }
@@ -55,24 +179,70 @@ impl BlockWidget for CodeBlock {
 
 impl Default for CodeBlock {
     fn default() -> Self {
-        let buffer = Buffer::new(None);
-        buffer.set_highlight_syntax(true);
+        #[cfg(feature = "sourceview")]
+        let source_view = {
+            let buffer = Buffer::new(None);
+            buffer.set_enable_undo(false);
+            buffer.create_tag(Some(LINE_HIGHLIGHT_TAG), &[("paragraph-background", &LINE_HIGHLIGHT_BACKGROUND)]);
 
-        if let Some(scheme) = StyleSchemeManager::new().scheme("classic-dark") {
-            buffer.set_style_scheme(Some(&scheme));
-        }
+            View::builder()
+                .buffer(&buffer)
+                .css_classes(["cmark-codeblock-sourceview"])
+                .valign(gtk4::Align::Fill)
+                .vexpand(true)
+                .editable(false)
+                .monospace(true)
+                .tab_width(4)
+                .show_line_numbers(true)
+                .highlight_current_line(true)
+                .background_pattern(BackgroundPatternType::None)
+                .build()
+        };
+
+        #[cfg(not(feature = "sourceview"))]
+        let source_view = {
+            let buffer = TextBuffer::new(None);
+            buffer.set_enable_undo(false);
+            buffer.create_tag(Some(MATCH_TAG), &[("background", &MATCH_BACKGROUND)]);
+            buffer.create_tag(Some(LINE_HIGHLIGHT_TAG), &[("paragraph-background", &LINE_HIGHLIGHT_BACKGROUND)]);
 
-        let source_view = View::builder()
-            .buffer(&buffer)
-            .css_classes(["cmark-codeblock-sourceview"])
-            .valign(gtk4::Align::Fill)
-            .vexpand(true)
-            .editable(false)
-            .monospace(true)
-            .tab_width(4)
-            .show_line_numbers(true)
-            .highlight_current_line(true)
-            .background_pattern(BackgroundPatternType::None)
+            View::builder()
+                .buffer(&buffer)
+                .css_classes(["cmark-codeblock-sourceview"])
+                .valign(gtk4::Align::Fill)
+                .vexpand(true)
+                .editable(false)
+                .monospace(true)
+                .build()
+        };
+
+        #[cfg(feature = "sourceview")]
+        let first_line = Rc::new(Cell::new(1_usize));
+
+        #[cfg(feature = "sourceview")]
+        let line_numbers = {
+            let line_numbers = GutterRendererText::builder()
+                .xalign(1.0)
+                .xpad(LINE_NUMBER_PADDING)
+                .visible(false)
+                .build();
+
+            line_numbers.connect_query_data(glib::clone!(
+                #[strong] first_line,
+                move |renderer, _, line| {
+                    renderer.set_text(&first_line.get().saturating_add(line as usize).to_string());
+                }
+            ));
+
+            ViewExt::gutter(&source_view, gtk4::TextWindowType::Left).insert(&line_numbers, 0);
+            line_numbers
+        };
+
+        let title = gtk4::Label::builder()
+            .css_classes(["cmark-codeblock-title"])
+            .halign(gtk4::Align::Start)
+            .ellipsize(gtk4::pango::EllipsizeMode::Middle)
+            .visible(false)
             .build();
 
         let root = gtk4::ScrolledWindow::builder()
@@ -93,15 +263,29 @@ impl Default for CodeBlock {
             .overflow(gtk4::Overflow::Hidden)
             .build();
 
+        container.append(&title);
         container.append(&root);
 
-        Self {
+        let block = Self {
             source_view,
-            line_cache: Rc::new(RefCell::new(HashMap::new())),
+            #[cfg(feature = "syntect")]
+            line_cache: Rc::new(RefCell::new(Vec::new())),
+            search: Rc::new(RefCell::new(None)),
+            backend: Rc::new(Cell::new(HighlightBackend::default())),
+            theme: Rc::new(Cell::new(CodeTheme::default())),
+            meta: Rc::new(RefCell::new(FenceMeta::default())),
+            #[cfg(feature = "sourceview")]
+            line_numbers,
+            #[cfg(feature = "sourceview")]
+            first_line,
             container,
             root,
+            title,
             lang: Mutable::new(None),
-        }
+        };
+
+        block.apply_highlighting();
+        block
     }
 }
 
@@ -111,37 +295,238 @@ impl CodeBlock {
         self.source_view.buffer()
     }
     
-    fn set_lang(&self, lang: Option<&String>) {
+    #[cfg(feature = "sourceview")]
+    fn source_buffer(&self) -> Buffer {
+        self.source_view.buffer()
+            .downcast::<Buffer>()
+            .expect("Buffer is not a SourceView5 Buffer")
+    }
+
+    /// Finds every match with GtkSourceView's search, which highlights them.
+    #[cfg(feature = "sourceview")]
+    fn search(&self, query: &str, options: &FindOptions) -> CodeSearch {
+        let buffer = self.source_buffer();
+        let settings = SearchSettings::builder()
+            .search_text(query)
+            .case_sensitive(options.case_sensitive)
+            .at_word_boundaries(options.whole_word)
+            .wrap_around(false)
+            .build();
+
+        let context = SearchContext::new(&buffer, Some(&settings));
+        context.set_highlight(true);
+
+        let mut matches = Vec::new();
+        let mut iter = buffer.start_iter();
+        while let Some((start, end, wrapped)) = context.forward(&iter) {
+            if wrapped || start.offset() == end.offset() {
+                break;
+            }
+
+            matches.push((start.offset(), end.offset()));
+            iter = end;
+        }
+
+        CodeSearch { context, matches }
+    }
+
+    #[cfg(feature = "sourceview")]
+    fn unhighlight(&self, search: CodeSearch) {
+        search.context.set_highlight(false);
+    }
+
+    /// Finds every match in the text, highlighting them with a tag.
+    #[cfg(not(feature = "sourceview"))]
+    fn search(&self, query: &str, options: &FindOptions) -> CodeSearch {
+        let buffer = self.buffer();
+        let text = self.text();
+        buffer.remove_tag_by_name(MATCH_TAG, &buffer.start_iter(), &buffer.end_iter());
+
+        let matches = crate::find::find_in_text(&text, query, options)
+            .into_iter()
+            .map(|range| {
+                let start = selection::byte_to_char_offset(&text, range.start) as i32;
+                let end = selection::byte_to_char_offset(&text, range.end) as i32;
+                buffer.apply_tag_by_name(MATCH_TAG, &buffer.iter_at_offset(start), &buffer.iter_at_offset(end));
+                (start, end)
+            })
+            .collect();
+
+        CodeSearch { matches }
+    }
+
+    #[cfg(not(feature = "sourceview"))]
+    fn unhighlight(&self, _search: CodeSearch) {
+        let buffer = self.buffer();
+        buffer.remove_tag_by_name(MATCH_TAG, &buffer.start_iter(), &buffer.end_iter());
+    }
+
+    /// Sets the highlighting backend and theme, re-highlighting if either changed.
+    pub(crate) fn set_highlighting(&self, backend: HighlightBackend, theme: CodeTheme) {
+        let changed = self.backend.replace(backend) != backend;
+        let changed = self.theme.replace(theme) != theme || changed;
+        if changed {
+            self.apply_highlighting();
+        }
+    }
+
+    fn apply_highlighting(&self) {
+        #[cfg(feature = "sourceview")]
+        {
+            let buffer = self.source_buffer();
+            buffer.set_highlight_syntax(self.backend.get().resolve() == HighlightBackend::SourceView);
+            buffer.set_style_scheme(highlight::source_scheme(self.theme.get()).as_ref());
+        }
+
+        self.refresh_syntect(0);
+    }
+
+    /// Re-colors the buffer with syntect from `from_line` on, since unlike
+    /// GtkSourceView's highlighting, its tags don't follow changes to the text.
+    #[cfg_attr(not(feature = "syntect"), allow(unused_variables))]
+    fn refresh_syntect(&self, from_line: usize) {
+        #[cfg(feature = "syntect")]
+        {
+            let buffer = self.buffer();
+            let mut line_cache = self.line_cache.borrow_mut();
+            if self.backend.get().resolve() == HighlightBackend::Syntect {
+                let lang = self.lang.get_cloned();
+                highlight::apply_syntect_tags(&buffer, lang.as_deref(), self.theme.get(), &mut line_cache, from_line);
+            } else {
+                line_cache.clear();
+                highlight::clear_syntect_tags(&buffer, &buffer.start_iter(), &buffer.end_iter());
+            }
+        }
+    }
+
+    /// Applies the fence's display options, re-applying the line highlights
+    /// when the text changed as well.
+    fn set_meta(&self, meta: FenceMeta, text_changed: bool) {
+        let meta_changed = *self.meta.borrow() != meta;
+        if meta_changed {
+            self.title.set_text(meta.title.as_deref().unwrap_or_default());
+            self.title.set_visible(meta.title.is_some());
+
+            #[cfg(feature = "sourceview")]
+            self.set_line_numbers(meta.line_numbers.unwrap_or(true), meta.start.unwrap_or(1));
+            self.meta.replace(meta);
+        }
+
+        if meta_changed || text_changed {
+            self.highlight_lines();
+            #[cfg(feature = "sourceview")]
+            self.update_line_numbers_width();
+        }
+    }
+
+    /// Shows GtkSourceView's line numbers when counting from 1, or the custom
+    /// line numbers otherwise.
+    #[cfg(feature = "sourceview")]
+    fn set_line_numbers(&self, visible: bool, start: usize) {
+        self.first_line.set(start);
+        let custom = visible && start != 1;
+        self.source_view.set_show_line_numbers(visible && !custom);
+        self.line_numbers.set_visible(custom);
+        self.line_numbers.queue_draw();
+    }
+
+    /// Fits the custom line numbers to the widest one.
+    #[cfg(feature = "sourceview")]
+    fn update_line_numbers_width(&self) {
+        if !self.line_numbers.is_visible() {
+            return;
+        }
+
+        let last_line = self.first_line.get().saturating_add(self.buffer().line_count() as usize - 1);
+        let (width, _) = GutterRendererTextExt::measure(&self.line_numbers, &last_line.to_string());
+        self.line_numbers.set_width_request(width + LINE_NUMBER_PADDING * 2);
+    }
+
+    /// Tags the lines highlighted by the fence's meta.
+    fn highlight_lines(&self) {
+        let buffer = self.buffer();
+        let tag_table = buffer.tag_table();
+        let (start, end) = buffer.bounds();
+        buffer.remove_tag_by_name(LINE_HIGHLIGHT_TAG, &start, &end);
+
+        let Some(tag) = tag_table.lookup(LINE_HIGHLIGHT_TAG) else {
+            return;
+        };
+        // Keep the highlight above syntect's backgrounds, which are created later.
+        tag.set_priority(tag_table.size() - 1);
+
+        for range in &self.meta.borrow().highlight_lines {
+            let first = i32::try_from((*range.start()).max(1) - 1).ok();
+            let last = i32::try_from((*range.end()).max(1) - 1).ok();
+            let Some(start) = first.and_then(|first| buffer.iter_at_line(first)) else {
+                continue;
+            };
+
+            let mut end = last.and_then(|last| buffer.iter_at_line(last)).unwrap_or_else(|| buffer.end_iter());
+            end.forward_line();
+            buffer.apply_tag(&tag, &start, &end);
+        }
+    }
+
+    /// Sets the language, returning whether it changed.
+    fn set_lang(&self, lang: Option<&String>) -> bool {
         let old_lang = self.lang.clone();
         if old_lang.get_cloned() == lang.cloned() {
-            return;
+            return false;
         }
         
         self.lang.set(lang.cloned());
-        let buffer = self.source_view.buffer();
-        let buffer = buffer
-            .downcast_ref::<Buffer>()
-            .expect("Buffer is not a SourceView5 Buffer");
-
-        if let Some(language) = LanguageManager::new().language(lang.unwrap_or(&"plaintext".to_owned())) {
-            buffer.set_language(Some(&language));
-        } else {
-            buffer.set_language(None);
+
+        #[cfg(feature = "sourceview")]
+        {
+            let language = lang.and_then(|lang| highlight::source_language(lang));
+            self.source_buffer().set_language(language.as_ref());
         }
-        self.line_cache.borrow_mut().clear();
+        true
     }
     
-    fn set_markup(&self, code: &str) {
+    /// Sets the code, returning the first line that changed, if any.
+    ///
+    /// Only the part between the common prefix and suffix of the old and new
+    /// code is replaced, so streamed code is appended at the end instead of
+    /// re-highlighting everything and resetting the scroll position.
+    fn set_markup(&self, code: &str) -> Option<usize> {
         let buffer = self.source_view.buffer();
         let start = buffer.start_iter();
         let end = buffer.end_iter();
         let include_hidden_chars = true;
-        if buffer.text(&start, &end, include_hidden_chars) != code {
-            buffer.set_text(code);
+        let old = buffer.text(&start, &end, include_hidden_chars);
+        if old == code {
+            return None;
         }
+
+        let (prefix, suffix) = common_affixes(&old, code);
+        let mut start = buffer.iter_at_offset(old[..prefix].chars().count() as i32);
+        let mut end = buffer.iter_at_offset(old[..old.len() - suffix].chars().count() as i32);
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, &code[prefix..code.len() - suffix]);
+
+        Some(old[..prefix].matches('\n').count())
     }
 }
 
+/// Gets the byte lengths of the longest common prefix and suffix of two
+/// strings, without letting them overlap.
+fn common_affixes(old: &str, new: &str) -> (usize, usize) {
+    let prefix = old.char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+
+    let suffix = old[prefix..].chars().rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+
+    (prefix, suffix)
+}
+
 pub struct CodeBlockFactory;
 impl BlockWidgetFactory for CodeBlockFactory {
     fn create(&self) -> Box<dyn BlockWidget> {
@@ -151,4 +536,47 @@ impl BlockWidgetFactory for CodeBlockFactory {
     fn matches(&self, node: &Node) -> bool {
         matches!(node, Node::Code(_))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_strings_are_all_prefix() {
+        assert_eq!(common_affixes("", ""), (0, 0));
+        assert_eq!(common_affixes("fn main() {}", "fn main() {}"), (12, 0));
+    }
+
+    #[test]
+    fn appending_keeps_the_old_text_as_prefix() {
+        assert_eq!(common_affixes("let a", "let a = 1;"), (5, 0));
+        assert_eq!(common_affixes("", "new"), (0, 0));
+    }
+
+    #[test]
+    fn changing_the_start_keeps_the_rest_as_suffix() {
+        assert_eq!(common_affixes("let a = 1;", "const a = 1;"), (0, 8));
+        assert_eq!(common_affixes("old line\nrest", "rest"), (0, 4));
+    }
+
+    #[test]
+    fn prefix_and_suffix_never_overlap() {
+        assert_eq!(common_affixes("aa", "aaa"), (2, 0));
+        assert_eq!(common_affixes("aaa", "aa"), (2, 0));
+        assert_eq!(common_affixes("abab", "ab"), (2, 0));
+    }
+
+    #[test]
+    fn affixes_end_on_char_boundaries() {
+        // `é` and `è` share their first byte, which must not count as common.
+        assert_eq!(common_affixes("é", "è"), (0, 0));
+        assert_eq!(common_affixes("日本語", "日本人語"), (6, 3));
+        assert_eq!(common_affixes("a 🦀 b", "a 🦞 b"), (2, 2));
+
+        let (old, new) = ("naïve ünïcode", "naïve unicode");
+        let (prefix, suffix) = common_affixes(old, new);
+        assert!(old.is_char_boundary(prefix) && new.is_char_boundary(prefix));
+        assert!(old.is_char_boundary(old.len() - suffix) && new.is_char_boundary(new.len() - suffix));
+        assert_eq!(&old[old.len() - suffix..], &new[new.len() - suffix..]);
+    }
+}
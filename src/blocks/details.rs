@@ -0,0 +1,153 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use gtk4::glib;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use markdown::mdast::{MdxJsxFlowElement, Node, Root};
+
+use crate::find::FindOptions;
+use crate::html;
+use crate::images::ImageResolver;
+use crate::view::MarkdownView;
+use super::{BlockWidget, BlockWidgetFactory};
+
+/// A `<details>` section, rendered as an expander around a nested view.
+#[derive(Debug, Clone)]
+pub(crate) struct DetailsBlock {
+    root: gtk4::Expander,
+    summary: gtk4::Label,
+    view: MarkdownView,
+    /// Whether the expanded state has been set from the `open` attribute,
+    /// after which it is left to the user.
+    initialized: Rc<Cell<bool>>,
+}
+
+impl BlockWidget for DetailsBlock {
+    fn root(&self) -> &gtk4::Widget {
+        self.root.upcast_ref()
+    }
+
+    fn update(&mut self, node: &Node) {
+        if let Node::MdxJsxFlowElement(element) = node {
+            self.set_details(element);
+        }
+    }
+
+    fn valid_node(&self, node: &Node) -> bool {
+        html::is_element(node, "details")
+    }
+
+    fn find(&self, query: &str, options: &FindOptions) -> usize {
+        self.view.find(query, options)
+    }
+
+    fn select_match(&self, index: usize) -> Option<gtk4::Widget> {
+        self.root.set_expanded(true);
+        self.view.select_match(index);
+        Some(self.root.clone().upcast())
+    }
+
+    fn clear_find(&self) {
+        self.view.clear_find();
+    }
+
+    /// Gets the summary followed by the text of the nested view, so
+    /// selecting and copying a section includes its body.
+    fn text(&self) -> String {
+        let summary = self.summary.text();
+        let body = self.view.blocks_text();
+        if body.is_empty() {
+            summary.into()
+        } else {
+            format!("{}\n\n{}", summary, body)
+        }
+    }
+
+    fn clone(&self) -> Box<dyn BlockWidget> {
+        Box::new(Self {
+            root: self.root.clone(),
+            summary: self.summary.clone(),
+            view: self.view.clone(),
+            initialized: self.initialized.clone(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Default for DetailsBlock {
+    fn default() -> Self {
+        let summary = gtk4::Label::builder()
+            .css_classes(["cmark-details-summary"])
+            .wrap(true)
+            .xalign(0.0)
+            .build();
+
+        let view = MarkdownView::default();
+        view.set_margin_top(8);
+
+        let root = gtk4::Expander::builder()
+            .css_classes(["cmark-details"])
+            .label_widget(&summary)
+            .child(&view)
+            .build();
+
+        Self {
+            root,
+            summary,
+            view,
+            initialized: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl DetailsBlock {
+    /// Keeps the nested view's rendering options in sync with `parent`.
+    pub fn bind_options(&self, parent: &MarkdownView) {
+        for property in [
+            "merge-paragraphs",
+            "inline-html-policy",
+            "html-policy",
+            "highlight-backend",
+            "code-theme",
+        ] {
+            parent.bind_property(property, &self.view, property)
+                .flags(glib::BindingFlags::SYNC_CREATE)
+                .build();
+        }
+    }
+
+    /// Sets the resolver `<img>` sources in the nested view are loaded through.
+    pub fn set_image_resolver(&self, resolver: Option<ImageResolver>) {
+        self.view.imp().set_image_resolver(resolver);
+    }
+
+    fn set_details(&self, element: &MdxJsxFlowElement) {
+        let summary = html::element_attribute(element, "summary")
+            .filter(|summary| !summary.is_empty())
+            .unwrap_or("Details");
+        self.summary.set_text(summary);
+
+        if !self.initialized.replace(true) {
+            self.root.set_expanded(html::element_attribute(element, "open").is_some());
+        }
+
+        self.view.set_mdast(Node::Root(Root {
+            children: element.children.clone(),
+            position: element.position.clone(),
+        }));
+    }
+}
+
+pub struct DetailsBlockFactory;
+impl BlockWidgetFactory for DetailsBlockFactory {
+    fn create(&self) -> Box<dyn BlockWidget> {
+        Box::new(DetailsBlock::default())
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        html::is_element(node, "details")
+    }
+}
@@ -0,0 +1,184 @@
+use std::iter::Peekable;
+use std::ops::RangeInclusive;
+use std::str::Chars;
+
+/// Keys that list the lines to highlight, as in `hl_lines="3 5-7"`.
+const HIGHLIGHT_KEYS: [&str; 5] = ["hl_lines", "highlight", "hl", "lines", "mark"];
+/// Keys that toggle line numbers, as in `linenos=false` or `showLineNumbers`.
+const LINE_NUMBER_KEYS: [&str; 5] = ["linenos", "linenums", "linenumbers", "showlinenumbers", "numberlines"];
+/// Keys that set the first line number, as in `start=10`.
+const START_KEYS: [&str; 4] = ["start", "linenostart", "startline", "firstline"];
+/// Keys that set the filename header, as in `title="main.rs"`.
+const TITLE_KEYS: [&str; 4] = ["title", "filename", "file", "caption"];
+
+/// Display options from the meta part of a fence's info string, e.g.
+/// ```` ```rust {3,5-7} linenos=false start=10 title="main.rs" ````.
+///
+/// Keys are matched case-insensitively, and unknown keys are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct FenceMeta {
+    /// The lines to highlight, numbered from 1 regardless of `start`.
+    pub highlight_lines: Vec<RangeInclusive<usize>>,
+    /// Whether to show line numbers, if set.
+    pub line_numbers: Option<bool>,
+    /// The number shown for the first line, if set.
+    pub start: Option<usize>,
+    /// The filename or title shown above the code.
+    pub title: Option<String>,
+}
+
+impl FenceMeta {
+    /// Splits a fence's language from any options written right after it
+    /// without a space, e.g. `rust{3}`, and parses them along with the meta.
+    pub fn parse(lang: Option<&str>, meta: Option<&str>) -> (Option<String>, Self) {
+        let (lang, attached) = match lang.and_then(|lang| lang.find('{').map(|i| lang.split_at(i))) {
+            Some((lang, attached)) => (Some(lang), attached),
+            None => (lang, ""),
+        };
+
+        let mut fence_meta = Self::default();
+        fence_meta.parse_options(attached);
+        fence_meta.parse_options(meta.unwrap_or_default());
+
+        let lang = lang.filter(|lang| !lang.is_empty()).map(str::to_owned);
+        (lang, fence_meta)
+    }
+
+    fn parse_options(&mut self, meta: &str) {
+        let mut chars = meta.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let key = take_while(&mut chars, |c| !c.is_whitespace() && c != '=' && c != '{');
+            let value = if chars.next_if_eq(&'=').is_some() || chars.peek() == Some(&'{') {
+                Some(parse_value(&mut chars))
+            } else {
+                None
+            };
+
+            self.set_option(&key.to_lowercase(), value.as_deref());
+        }
+    }
+
+    fn set_option(&mut self, key: &str, value: Option<&str>) {
+        if key.is_empty() || HIGHLIGHT_KEYS.contains(&key) {
+            self.highlight_lines.extend(value.map(parse_line_ranges).unwrap_or_default());
+        } else if LINE_NUMBER_KEYS.contains(&key) {
+            // `showLineNumbers{10}` both shows line numbers and sets the start.
+            let start = value.and_then(|value| value.trim().parse().ok());
+            if start.is_some() {
+                self.start = start;
+            }
+
+            self.line_numbers = Some(start.is_some() || value.is_none_or(|value| {
+                !matches!(value.to_lowercase().as_str(), "false" | "no" | "off" | "0")
+            }));
+        } else if START_KEYS.contains(&key) {
+            self.start = value.and_then(|value| value.trim().parse().ok()).or(self.start);
+        } else if TITLE_KEYS.contains(&key) {
+            self.title = value.filter(|value| !value.is_empty()).map(str::to_owned);
+        }
+    }
+}
+
+/// Parses a value, which is either quoted, braced, or runs until whitespace.
+fn parse_value(chars: &mut Peekable<Chars>) -> String {
+    let close = match chars.peek() {
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('{') => '}',
+        _ => return take_while(chars, |c| !c.is_whitespace()),
+    };
+
+    chars.next();
+    let value = take_while(chars, |c| c != close);
+    chars.next();
+    value
+}
+
+fn take_while(chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> String {
+    let mut value = String::new();
+    while let Some(c) = chars.next_if(|c| predicate(*c)) {
+        value.push(c);
+    }
+    value
+}
+
+/// Parses line ranges separated by commas or whitespace, e.g. `3,5-7`.
+fn parse_line_ranges(value: &str) -> Vec<RangeInclusive<usize>> {
+    value.split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|part| {
+            let part = part.trim_matches(|c| c == '{' || c == '}');
+            match part.split_once(['-', ':']) {
+                Some((start, end)) => Some(start.trim().parse().ok()?..=end.trim().parse().ok()?),
+                None => part.parse().ok().map(|line| line..=line),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lang: &str, meta: &str) -> (Option<String>, FenceMeta) {
+        FenceMeta::parse(Some(lang), Some(meta))
+    }
+
+    #[test]
+    fn lang_only() {
+        assert_eq!(FenceMeta::parse(Some("rust"), None), (Some("rust".to_owned()), FenceMeta::default()));
+        assert_eq!(FenceMeta::parse(None, None), (None, FenceMeta::default()));
+    }
+
+    #[test]
+    fn line_ranges() {
+        let (lang, meta) = parse("rust", "{1,3-5}");
+        assert_eq!(lang.as_deref(), Some("rust"));
+        assert_eq!(meta.highlight_lines, [1..=1, 3..=5]);
+
+        let (lang, meta) = parse("rust{2}", r#"hl_lines="7 9:10""#);
+        assert_eq!(lang.as_deref(), Some("rust"));
+        assert_eq!(meta.highlight_lines, [2..=2, 7..=7, 9..=10]);
+    }
+
+    #[test]
+    fn reversed_and_garbage_ranges() {
+        let (_, meta) = parse("rust", "{5-3,a-b,x,-,4-}");
+        assert_eq!(meta.highlight_lines, [RangeInclusive::new(5, 3)]);
+        assert!(meta.highlight_lines.iter().all(|range| range.clone().next().is_none()));
+
+        let (lang, meta) = parse("{1}", "");
+        assert_eq!(lang, None);
+        assert_eq!(meta.highlight_lines, [1..=1]);
+    }
+
+    #[test]
+    fn quoted_titles() {
+        let (_, meta) = parse("rust", r#"title="src/main file.rs" linenos=false"#);
+        assert_eq!(meta.title.as_deref(), Some("src/main file.rs"));
+        assert_eq!(meta.line_numbers, Some(false));
+
+        let (_, meta) = parse("rust", "Filename='a b' showLineNumbers");
+        assert_eq!(meta.title.as_deref(), Some("a b"));
+        assert_eq!(meta.line_numbers, Some(true));
+
+        let (_, meta) = parse("rust", r#"title="""#);
+        assert_eq!(meta.title, None);
+    }
+
+    #[test]
+    fn start_lines() {
+        let (_, meta) = parse("rust", "showLineNumbers{10}");
+        assert_eq!((meta.line_numbers, meta.start), (Some(true), Some(10)));
+
+        let (_, meta) = parse("rust", &format!("start={}", usize::MAX));
+        assert_eq!(meta.start, Some(usize::MAX));
+
+        let (_, meta) = parse("rust", "start=99999999999999999999999999 linenostart=-1");
+        assert_eq!(meta.start, None);
+    }
+}
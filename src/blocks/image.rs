@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use gtk4::{gdk, gio, glib};
+use gtk4::prelude::*;
+use markdown::mdast::{MdxJsxFlowElement, Node};
+
+use crate::html;
+use crate::images::{self, ImageResolver, ImageSource};
+use super::{BlockWidget, BlockWidgetFactory};
+
+/// An `<img>` from rendered HTML, loaded through the view's image resolver.
+#[derive(Clone)]
+pub(crate) struct ImageBlock {
+    root: gtk4::Picture,
+    src: Rc<RefCell<String>>,
+    resolver: Rc<RefCell<Option<ImageResolver>>>,
+    /// Cancels loading the previous source when the source changes.
+    cancellable: Rc<RefCell<Option<gio::Cancellable>>>,
+}
+
+impl BlockWidget for ImageBlock {
+    fn root(&self) -> &gtk4::Widget {
+        self.root.upcast_ref()
+    }
+
+    fn update(&mut self, node: &Node) {
+        if let Node::MdxJsxFlowElement(element) = node {
+            self.set_image(element);
+        }
+    }
+
+    fn valid_node(&self, node: &Node) -> bool {
+        html::is_element(node, "img")
+    }
+
+    fn text(&self) -> String {
+        self.root.alternative_text().map(Into::into).unwrap_or_default()
+    }
+
+    fn clone(&self) -> Box<dyn BlockWidget> {
+        Box::new(Self {
+            root: self.root.clone(),
+            src: self.src.clone(),
+            resolver: self.resolver.clone(),
+            cancellable: self.cancellable.clone(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Default for ImageBlock {
+    fn default() -> Self {
+        let root = gtk4::Picture::builder()
+            .css_classes(["cmark-image"])
+            .halign(gtk4::Align::Start)
+            .can_shrink(true)
+            .build();
+
+        Self {
+            root,
+            src: Rc::new(RefCell::new(String::new())),
+            resolver: Rc::new(RefCell::new(None)),
+            cancellable: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+impl ImageBlock {
+    /// Sets the resolver sources are loaded through, reloading the image if
+    /// it changed.
+    pub fn set_resolver(&self, resolver: Option<ImageResolver>) {
+        let unchanged = match (&*self.resolver.borrow(), &resolver) {
+            (Some(old), Some(new)) => std::ptr::addr_eq(Rc::as_ptr(old), Rc::as_ptr(new)),
+            (old, new) => old.is_none() && new.is_none(),
+        };
+
+        if !unchanged {
+            self.resolver.replace(resolver);
+            self.load();
+        }
+    }
+
+    fn set_image(&self, element: &MdxJsxFlowElement) {
+        let src = html::element_attribute(element, "src").unwrap_or_default();
+        if *self.src.borrow() != src {
+            self.src.replace(src.to_owned());
+            self.load();
+        }
+
+        let alt = html::element_attribute(element, "alt");
+        let title = html::element_attribute(element, "title").or(alt);
+        self.root.set_alternative_text(alt);
+        self.root.set_tooltip_text(title.filter(|title| !title.is_empty()));
+
+        let size = |name| html::element_attribute(element, name)
+            .and_then(|size| size.trim_end_matches("px").parse::<i32>().ok())
+            .unwrap_or(-1);
+        self.root.set_size_request(size("width"), size("height"));
+    }
+
+    /// Loads the current source asynchronously, so remote and large images
+    /// don't block the main thread.
+    fn load(&self) {
+        if let Some(cancellable) = self.cancellable.take() {
+            cancellable.cancel();
+        }
+        self.root.set_paintable(None::<&gdk::Paintable>);
+
+        match images::resolve(&self.src.borrow(), self.resolver.borrow().as_ref()) {
+            Some(ImageSource::Data(bytes)) => self.root.set_paintable(gdk::Texture::from_bytes(&bytes).ok().as_ref()),
+            Some(ImageSource::File(file)) => {
+                let cancellable = gio::Cancellable::new();
+                file.load_bytes_async(Some(&cancellable), glib::clone!(
+                    #[weak(rename_to = picture)] self.root,
+                    move |result| if let Ok((bytes, _)) = result {
+                        picture.set_paintable(gdk::Texture::from_bytes(&bytes).ok().as_ref());
+                    }
+                ));
+                self.cancellable.replace(Some(cancellable));
+            },
+            None => {},
+        }
+    }
+}
+
+pub struct ImageBlockFactory;
+impl BlockWidgetFactory for ImageBlockFactory {
+    fn create(&self) -> Box<dyn BlockWidget> {
+        Box::new(ImageBlock::default())
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        html::is_element(node, "img")
+    }
+}
@@ -1,23 +1,75 @@
 mod code;
+mod details;
+mod fence;
+mod image;
 mod text;
 mod table;
 mod thematicbreak;
 
 use std::any::Any;
 use std::fmt::{Debug, Formatter, Result};
+use std::ops::Range;
+use std::sync::LazyLock;
 use markdown::mdast::Node;
 
+use crate::find::FindOptions;
+
 pub use code::CodeBlock;
 pub(crate) use code::CodeBlockFactory;
+pub(crate) use details::{DetailsBlock, DetailsBlockFactory};
+pub(crate) use image::{ImageBlock, ImageBlockFactory};
 pub(crate) use text::TextBlockFactory;
 pub(crate) use table::TableBlockFactory;
 pub(crate) use thematicbreak::ThematicBreakBlockFactory;
 
+static FACTORIES: LazyLock<Vec<Box<dyn BlockWidgetFactory + Send + Sync>>> = LazyLock::new(|| vec![
+    Box::new(TextBlockFactory),
+    Box::new(CodeBlockFactory),
+    Box::new(TableBlockFactory),
+    Box::new(ThematicBreakBlockFactory),
+    Box::new(ImageBlockFactory),
+    Box::new(DetailsBlockFactory),
+]);
+
+/// Creates a block widget for the node using the first factory that matches it.
+pub(crate) fn create_block_widget(node: &Node) -> Option<Box<dyn BlockWidget>> {
+    FACTORIES.iter()
+        .find(|factory| factory.matches(node))
+        .map(|factory| factory.create())
+}
+
 pub(crate) trait BlockWidget: Any {
     fn root(&self) -> &gtk4::Widget;
     fn update(&mut self, node: &Node);
     fn valid_node(&self, node: &Node) -> bool;
 
+    /// Highlights every match of `query`, returning the number of matches.
+    fn find(&self, _query: &str, _options: &FindOptions) -> usize {
+        0
+    }
+
+    /// Marks the `index`th match as the current one, returning the widget containing it.
+    fn select_match(&self, _index: usize) -> Option<gtk4::Widget> {
+        None
+    }
+
+    /// Removes any find highlighting.
+    fn clear_find(&self) {}
+
+    /// Gets the plain text content of the block.
+    fn text(&self) -> String {
+        String::new()
+    }
+
+    /// Selects a byte range of the block's text, or clears the selection.
+    fn select(&self, _range: Option<Range<usize>>) {}
+
+    /// Gets the byte offset into the block's text that is closest to a point
+    /// in the coordinates of `root()`.
+    fn offset_at(&self, _x: f64, _y: f64) -> Option<usize> {
+        None
+    }
+
     fn clone(&self) -> Box<dyn BlockWidget>;
     fn as_any(&self) -> &dyn Any;
 }
@@ -1,14 +1,17 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, ops::Range, rc::Rc};
 use markdown::mdast::{Node, Table};
 use gtk4::prelude::*;
 
+use crate::find::{FindOptions, LabelMatches};
+use crate::inline::{InlineContent, InlineLabel};
+use crate::selection;
 use super::{BlockWidget, BlockWidgetFactory};
-use super::super::util;
 
 #[derive(Debug, Clone)]
 struct TableBlock {
     root: gtk4::Grid,
-    rows: Rc<RefCell<Vec<Vec<gtk4::Label>>>>,
+    rows: Rc<RefCell<Vec<Vec<InlineLabel>>>>,
+    matches: LabelMatches,
 }
 
 impl BlockWidget for TableBlock {
@@ -26,10 +29,36 @@ impl BlockWidget for TableBlock {
         matches!(node, Node::Table(_))
     }
 
+    fn find(&self, query: &str, options: &FindOptions) -> usize {
+        let cells = self.rows.borrow().concat();
+        self.matches.find(&cells, query, options)
+    }
+
+    fn select_match(&self, index: usize) -> Option<gtk4::Widget> {
+        self.matches.select(index)
+    }
+
+    fn clear_find(&self) {
+        self.matches.clear();
+    }
+
+    fn text(&self) -> String {
+        selection::grid_text(&self.cell_labels())
+    }
+
+    fn select(&self, range: Option<Range<usize>>) {
+        selection::grid_select(&self.cell_labels(), range);
+    }
+
+    fn offset_at(&self, x: f64, y: f64) -> Option<usize> {
+        selection::grid_offset_at(self.root.upcast_ref(), &self.cell_labels(), x, y)
+    }
+
     fn clone(&self) -> Box<dyn BlockWidget> {
         Box::new(Self {
             root: self.root.clone(),
             rows: self.rows.clone(),
+            matches: self.matches.clone(),
         })
     }
 
@@ -49,6 +78,7 @@ impl Default for TableBlock {
         Self {
             root,
             rows: Rc::new(RefCell::new(Vec::new())),
+            matches: LabelMatches::default(),
         }
     }
 }
@@ -65,25 +95,23 @@ impl TableBlock {
         for (r, row) in rows.iter().enumerate() {
             for (c, cell) in row.children().unwrap().iter().enumerate() {
                 let cell_label = &self.rows.borrow()[r][c];
-                let mut cell_text = String::new();
-
-                for child in cell.children().unwrap() {
-                    cell_text.push_str(&util::inline_node_to_pango_markup(child));
-                }
-
-                // Only overwrite the label's text if it has changed.
-                if cell_label.label() != cell_text {
-                    cell_label.set_markup(&cell_text);
-                }
+                cell_label.set_content(InlineContent::from_nodes(cell.children().unwrap()), None);
             }
         }
     }
 
+    /// Gets the labels of every cell, row by row.
+    fn cell_labels(&self) -> Vec<Vec<gtk4::Label>> {
+        self.rows.borrow().iter()
+            .map(|row| row.iter().map(|cell| cell.label.clone()).collect())
+            .collect()
+    }
+
     fn ensure_rows(&self, row_count: usize, col_count: usize) {
         while self.rows.borrow().len() > row_count {
             if let Some(row) = self.rows.borrow_mut().pop() {
                 for cell in row {
-                    self.root.remove(&cell);
+                    self.root.remove(&cell.label);
                 }
             }
         }
@@ -91,7 +119,7 @@ impl TableBlock {
         for r in 0..self.rows.borrow().len() {
             while self.rows.borrow()[r].len() > col_count {
                 if let Some(cell) = self.rows.borrow_mut()[r].pop() {
-                    self.root.remove(&cell);
+                    self.root.remove(&cell.label);
                 }
             }
         }
@@ -115,7 +143,7 @@ impl TableBlock {
                     .build();
             
                 self.root.attach(&label, c as i32, r as i32, 1, 1);
-                self.rows.borrow_mut()[r].push(label);
+                self.rows.borrow_mut()[r].push(InlineLabel::new(label));
             }
         }
     }
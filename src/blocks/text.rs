@@ -1,21 +1,26 @@
+use std::ops::Range;
 use gtk4::prelude::*;
 use markdown::mdast::Node;
 
+use crate::find::{FindOptions, LabelMatches};
+use crate::inline::{InlineContent, InlineLabel};
+use crate::selection;
 use super::{BlockWidget, BlockWidgetFactory};
-use super::super::util;
-
-const HEADER_SIZES: [(usize, &str); 6] = [
-    (1, "170%"),
-    (2, "150%"),
-    (3, "125%"),
-    (4, "110%"),
-    (5, "100%"),
-    (6, "90%"),
+
+const HEADER_SIZES: [(usize, f64); 6] = [
+    (1, 1.7),
+    (2, 1.5),
+    (3, 1.25),
+    (4, 1.1),
+    (5, 1.0),
+    (6, 0.9),
 ];
 
 #[derive(Debug, Clone)]
 struct TextBlock {
     root: gtk4::Label,
+    inline: InlineLabel,
+    matches: LabelMatches,
 }
 
 impl BlockWidget for TextBlock {
@@ -35,9 +40,35 @@ impl BlockWidget for TextBlock {
         matches!(node, Node::Paragraph(_) | Node::Heading(_))
     }
 
+    fn find(&self, query: &str, options: &FindOptions) -> usize {
+        self.matches.find(std::slice::from_ref(&self.inline), query, options)
+    }
+
+    fn select_match(&self, index: usize) -> Option<gtk4::Widget> {
+        self.matches.select(index)
+    }
+
+    fn clear_find(&self) {
+        self.matches.clear();
+    }
+
+    fn text(&self) -> String {
+        self.root.text().into()
+    }
+
+    fn select(&self, range: Option<Range<usize>>) {
+        selection::grid_select(&[vec![self.root.clone()]], range);
+    }
+
+    fn offset_at(&self, x: f64, y: f64) -> Option<usize> {
+        Some(selection::label_offset_at(&self.root, x, y))
+    }
+
     fn clone(&self) -> Box<dyn BlockWidget> {
         Box::new(Self {
             root: self.root.clone(),
+            inline: self.inline.clone(),
+            matches: self.matches.clone(),
         })
     }
 
@@ -60,37 +91,42 @@ impl Default for TextBlock {
             .build();
 
         Self {
+            inline: InlineLabel::new(root.clone()),
             root,
+            matches: LabelMatches::default(),
         }
     }
 }
 
 impl TextBlock {
     pub fn set_from_paragraph(&self, paragraph: &markdown::mdast::Paragraph) {
-        let mut buffer = String::new();
-        for inline in &paragraph.children {
-            buffer.push_str(&util::inline_node_to_pango_markup(inline));
-        }
-
-        // Only overwrite the label's text if it has changed.
-        if self.root.label() != buffer {
-            self.root.set_markup(&buffer);
-        }
+        self.set_kind_classes(&["cmark-paragraph"]);
+        self.inline.set_content(InlineContent::from_nodes(&paragraph.children), None);
     }
 
     pub fn set_from_heading(&self, heading: &markdown::mdast::Heading) {
-        let heading_size = HEADER_SIZES
+        let heading_scale = HEADER_SIZES
             .get(heading.depth as usize - 1)
-            .map_or("100%", |(_, size)| *size);
+            .map_or(1.0, |(_, scale)| *scale);
+
+        let level_class = format!("cmark-heading-{}", heading.depth);
+        self.set_kind_classes(&["cmark-heading", &level_class]);
+        self.inline.set_content(InlineContent::from_nodes(&heading.children), Some(heading_scale));
+    }
 
-        let mut buffer = String::new();
-        for inline in &heading.children {
-            buffer.push_str(&util::inline_node_to_pango_markup(inline));
+    /// Replaces the classes describing the kind of text block, so spacing
+    /// between paragraphs and headings can be styled from CSS.
+    fn set_kind_classes(&self, classes: &[&str]) {
+        for class in self.root.css_classes() {
+            if (class == "cmark-paragraph" || class.starts_with("cmark-heading"))
+                && !classes.contains(&class.as_str())
+            {
+                self.root.remove_css_class(&class);
+            }
         }
 
-        // Only overwrite the label's text if it has changed.
-        if self.root.label() != buffer {
-            self.root.set_markup(&format!("<span size=\"{}\">{}</span>", heading_size, buffer));
+        for class in classes {
+            self.root.add_css_class(class);
         }
     }
 }
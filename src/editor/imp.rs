@@ -0,0 +1,249 @@
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::time::Duration;
+use gtk4::glib::{self, Properties};
+use gtk4::subclass::prelude::*;
+use gtk4::prelude::*;
+use sourceview5::prelude::*;
+use sourceview5::{Buffer, LanguageManager, View};
+
+use crate::MarkdownView;
+use crate::util::get_widget_children;
+
+const DEFAULT_DEBOUNCE_MS: u32 = 150;
+
+#[derive(Properties)]
+#[properties(wrapper_type = super::MarkdownEditor)]
+pub struct MarkdownEditor {
+    pub(super) source_view: View,
+    pub(super) markdown_view: MarkdownView,
+    pub(super) paned: gtk4::Paned,
+    editor_window: gtk4::ScrolledWindow,
+    preview_window: gtk4::ScrolledWindow,
+    render_source: RefCell<Option<glib::SourceId>>,
+    syncing_scroll: Cell<bool>,
+
+    /// The markdown source being edited.
+    #[property(get = Self::text, set = Self::set_text, type = String)]
+    text: PhantomData<String>,
+
+    /// How long to wait after the last edit before re-rendering, in milliseconds.
+    #[property(get, set)]
+    debounce: Cell<u32>,
+
+    /// Whether the editor and preview scroll positions are kept in sync.
+    #[property(get, set)]
+    sync_scroll: Cell<bool>,
+}
+
+impl Default for MarkdownEditor {
+    fn default() -> Self {
+        let buffer = Buffer::new(None);
+        if let Some(language) = LanguageManager::new().language("markdown") {
+            buffer.set_language(Some(&language));
+        }
+
+        let source_view = View::builder()
+            .buffer(&buffer)
+            .css_classes(["cmark-editor-sourceview"])
+            .monospace(true)
+            .wrap_mode(gtk4::WrapMode::WordChar)
+            .tab_width(4)
+            .show_line_numbers(true)
+            .build();
+
+        let editor_window = gtk4::ScrolledWindow::builder()
+            .css_classes(["cmark-editor-window"])
+            .hscrollbar_policy(gtk4::PolicyType::Never)
+            .hexpand(true)
+            .vexpand(true)
+            .child(&source_view)
+            .build();
+
+        let markdown_view = MarkdownView::default();
+        let preview_window = gtk4::ScrolledWindow::builder()
+            .css_classes(["cmark-editor-preview"])
+            .hscrollbar_policy(gtk4::PolicyType::Never)
+            .hexpand(true)
+            .vexpand(true)
+            .child(&markdown_view)
+            .build();
+
+        let paned = gtk4::Paned::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .start_child(&editor_window)
+            .end_child(&preview_window)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+
+        Self {
+            source_view,
+            markdown_view,
+            paned,
+            editor_window,
+            preview_window,
+            render_source: RefCell::new(None),
+            syncing_scroll: Cell::new(false),
+            text: PhantomData,
+            debounce: Cell::new(DEFAULT_DEBOUNCE_MS),
+            sync_scroll: Cell::new(true),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for MarkdownEditor {
+    const NAME: &'static str = "MarkdownEditor";
+    type Type = super::MarkdownEditor;
+    type ParentType = gtk4::Box;
+}
+
+#[glib::derived_properties]
+impl ObjectImpl for MarkdownEditor {
+    fn constructed(&self) {
+        self.parent_constructed();
+        self.obj().add_css_class("cmark-editor");
+        self.obj().append(&self.paned);
+
+        self.source_view.buffer().connect_changed(glib::clone!(
+            #[weak(rename_to = editor)] self.obj(),
+            move |_| editor.imp().schedule_render()
+        ));
+
+        self.editor_window.vadjustment().connect_value_changed(glib::clone!(
+            #[weak(rename_to = editor)] self.obj(),
+            move |_| editor.imp().sync_preview_to_editor()
+        ));
+
+        self.preview_window.vadjustment().connect_value_changed(glib::clone!(
+            #[weak(rename_to = editor)] self.obj(),
+            move |_| editor.imp().sync_editor_to_preview()
+        ));
+    }
+
+    fn dispose(&self) {
+        if let Some(source) = self.render_source.take() {
+            source.remove();
+        }
+    }
+}
+
+impl WidgetImpl for MarkdownEditor {}
+
+impl BoxImpl for MarkdownEditor {}
+
+impl MarkdownEditor {
+    fn text(&self) -> String {
+        let buffer = self.source_view.buffer();
+        buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).into()
+    }
+
+    fn set_text(&self, text: String) {
+        self.source_view.buffer().set_text(&text);
+    }
+
+    /// Re-renders the preview once no edits have happened for `debounce` milliseconds.
+    fn schedule_render(&self) {
+        if let Some(source) = self.render_source.take() {
+            source.remove();
+        }
+
+        let delay = Duration::from_millis(self.debounce.get().into());
+        let source = glib::timeout_add_local_once(delay, glib::clone!(
+            #[weak(rename_to = editor)] self.obj(),
+            move || {
+                let imp = editor.imp();
+                imp.render_source.take();
+                imp.markdown_view.set_markdown(imp.text());
+            }
+        ));
+
+        self.render_source.replace(Some(source));
+    }
+
+    /// Runs `f` with the scroll sync guard held, so the adjustment it moves
+    /// does not bounce the change back to the other pane.
+    fn with_scroll_guard(&self, f: impl FnOnce()) {
+        if !self.sync_scroll.get() || self.syncing_scroll.get() {
+            return;
+        }
+
+        self.syncing_scroll.set(true);
+        f();
+        self.syncing_scroll.set(false);
+    }
+
+    /// Scrolls the preview to the block rendered from the topmost visible line.
+    ///
+    /// Blocks are matched by line rather than by byte offset, so a scroll
+    /// tick never has to copy the buffer's text.
+    fn sync_preview_to_editor(&self) {
+        self.with_scroll_guard(|| {
+            let top = self.source_view.visible_rect().y();
+            let (iter, line_top) = self.source_view.line_at_y(top);
+            let (_, line_height) = self.source_view.line_yrange(&iter);
+            // Source positions count lines from 1, text iters from 0.
+            let line = usize::try_from(iter.line()).unwrap_or(0) + 1;
+
+            let Some(block) = self.markdown_view.block_at_source_line(line) else {
+                return;
+            };
+            let Some(bounds) = block.compute_bounds(&self.markdown_view) else {
+                return;
+            };
+
+            // Interpolate within the block so long blocks scroll smoothly.
+            let within_line = if line_height > 0 {
+                (top - line_top) as f32 / line_height as f32
+            } else {
+                0.0
+            };
+            let fraction = self.markdown_view.source_lines_for_widget(&block)
+                .filter(|lines| !lines.is_empty())
+                .map_or(0.0, |lines| {
+                    (line.saturating_sub(lines.start) as f32 + within_line) / lines.len() as f32
+                })
+                .clamp(0.0, 1.0);
+
+            let y = bounds.y() + bounds.height() * fraction;
+            self.preview_window.vadjustment().set_value(y.into());
+        });
+    }
+
+    /// Scrolls the editor to the source of the topmost visible preview block.
+    fn sync_editor_to_preview(&self) {
+        self.with_scroll_guard(|| {
+            let top = self.preview_window.vadjustment().value() as f32;
+            let block = get_widget_children(&self.markdown_view)
+                .into_iter()
+                .filter_map(|child| {
+                    let bounds = child.compute_bounds(&self.markdown_view)?;
+                    Some((child, bounds))
+                })
+                .find(|(_, bounds)| bounds.y() + bounds.height() > top);
+
+            let Some((block, bounds)) = block else {
+                return;
+            };
+            let Some(lines) = self.markdown_view.source_lines_for_widget(&block) else {
+                return;
+            };
+
+            let fraction = if bounds.height() > 0.0 {
+                ((top - bounds.y()) / bounds.height()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+
+            let position = lines.start.saturating_sub(1) as f32 + lines.len() as f32 * fraction;
+            let line = i32::try_from(position as usize).unwrap_or(i32::MAX);
+
+            let buffer = self.source_view.buffer();
+            let iter = buffer.iter_at_line(line).unwrap_or_else(|| buffer.end_iter());
+            let (y, height) = self.source_view.line_yrange(&iter);
+            let y = y as f32 + height as f32 * position.fract();
+            self.editor_window.vadjustment().set_value(y.into());
+        });
+    }
+}
@@ -0,0 +1,41 @@
+mod imp;
+
+use gtk4::glib::subclass::types::ObjectSubclassIsExt as _;
+use gtk4::glib::{self, Object};
+
+use crate::MarkdownView;
+
+glib::wrapper! {
+    /// A markdown source editor paired with a live `MarkdownView` preview.
+    pub struct MarkdownEditor(ObjectSubclass<imp::MarkdownEditor>)
+        @extends gtk4::Widget, gtk4::Box,
+        @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget, gtk4::Orientable;
+}
+
+impl Default for MarkdownEditor {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
+
+impl MarkdownEditor {
+    /// Creates a new, empty `MarkdownEditor`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the source view used for editing the markdown.
+    pub fn source_view(&self) -> sourceview5::View {
+        self.imp().source_view.clone()
+    }
+
+    /// Gets the `MarkdownView` used for the live preview.
+    pub fn markdown_view(&self) -> MarkdownView {
+        self.imp().markdown_view.clone()
+    }
+
+    /// Gets the `Paned` separating the editor and the preview.
+    pub fn paned(&self) -> gtk4::Paned {
+        self.imp().paned.clone()
+    }
+}
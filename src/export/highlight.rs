@@ -0,0 +1,104 @@
+use gtk4::pango::{self, AttrColor, AttrInt, AttrList};
+#[cfg(feature = "sourceview")]
+use gtk4::prelude::*;
+#[cfg(feature = "sourceview")]
+use sourceview5::prelude::*;
+
+use crate::highlight::{self, CodeTheme};
+use crate::inline;
+
+/// A light theme, since exports are usually printed or shown on white.
+const CODE_THEME: CodeTheme = CodeTheme::Light;
+
+/// Gets the syntax highlighting of a code block as Pango attributes.
+///
+/// GtkSourceView is preferred when GTK has been initialized, otherwise code
+/// is highlighted with syntect if it was built, or left uncolored.
+pub(super) fn highlight_code(code: &str, lang: Option<&str>) -> AttrList {
+    #[cfg(feature = "sourceview")]
+    if gtk4::is_initialized_main_thread() {
+        return sourceview_attributes(code, lang);
+    }
+
+    #[cfg(feature = "syntect")]
+    {
+        syntect_attributes(code, lang)
+    }
+
+    #[cfg(not(feature = "syntect"))]
+    {
+        AttrList::new()
+    }
+}
+
+#[cfg(feature = "sourceview")]
+fn sourceview_attributes(code: &str, lang: Option<&str>) -> AttrList {
+    let attrs = AttrList::new();
+    let Some(language) = lang.and_then(highlight::source_language) else {
+        return attrs;
+    };
+
+    let buffer = sourceview5::Buffer::with_language(&language);
+    buffer.set_style_scheme(highlight::source_scheme(CODE_THEME).as_ref());
+    buffer.set_highlight_syntax(true);
+    buffer.set_text(code);
+    buffer.ensure_highlight(&buffer.start_iter(), &buffer.end_iter());
+
+    let offsets = code.char_indices()
+        .map(|(i, _)| i)
+        .chain([code.len()])
+        .collect::<Vec<_>>();
+    let byte_offset = |iter: &gtk4::TextIter| offsets.get(iter.offset() as usize).copied().unwrap_or(code.len());
+
+    let mut iter = buffer.start_iter();
+    while !iter.is_end() {
+        let mut next = iter;
+        next.forward_to_tag_toggle(None::<&gtk4::TextTag>);
+        let range = byte_offset(&iter)..byte_offset(&next);
+
+        for tag in iter.tags() {
+            if tag.is_foreground_set()
+                && let Some(color) = tag.foreground_rgba()
+            {
+                let channel = |value: f32| (value.clamp(0.0, 1.0) * 65535.0) as u16;
+                let attr = AttrColor::new_foreground(channel(color.red()), channel(color.green()), channel(color.blue()));
+                attrs.insert(inline::with_range(attr, &range));
+            }
+            if tag.is_weight_set() && tag.weight() >= 600 {
+                attrs.insert(inline::with_range(AttrInt::new_weight(pango::Weight::Bold), &range));
+            }
+            if tag.is_style_set() {
+                attrs.insert(inline::with_range(AttrInt::new_style(tag.style()), &range));
+            }
+        }
+
+        if next.offset() == iter.offset() {
+            break;
+        }
+        iter = next;
+    }
+
+    attrs
+}
+
+#[cfg(feature = "syntect")]
+fn syntect_attributes(code: &str, lang: Option<&str>) -> AttrList {
+    use syntect::highlighting::FontStyle;
+
+    let attrs = AttrList::new();
+    for (range, style) in highlight::syntect_highlight(code, lang, CODE_THEME) {
+        let color = style.foreground;
+        let channel = |value: u8| u16::from(value) * 257;
+        let attr = AttrColor::new_foreground(channel(color.r), channel(color.g), channel(color.b));
+        attrs.insert(inline::with_range(attr, &range));
+
+        if style.font_style.contains(FontStyle::BOLD) {
+            attrs.insert(inline::with_range(AttrInt::new_weight(pango::Weight::Bold), &range));
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            attrs.insert(inline::with_range(AttrInt::new_style(pango::Style::Italic), &range));
+        }
+    }
+
+    attrs
+}
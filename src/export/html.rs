@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use markdown::mdast::{AlignKind, Node, Root, Table};
+
+use crate::html;
+use crate::inline::{InlineContent, InlineStyle};
+use crate::ir::{AlertKind, RenderBlock, RenderBuffer};
+
+/// URL schemes links and images may use, besides relative URLs.
+const ALLOWED_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+const DEPTH_MULTIPLIER: usize = 16;
+const CONTINUATION_INDENT: usize = 16;
+
+/// Escapes text for use in HTML content and attribute values.
+/// Returns the URL if it's relative or uses an allowed scheme, so documents
+/// can't smuggle `javascript:` and the like into the exported HTML.
+fn allowed_url(url: &str) -> Option<&str> {
+    // Browsers ignore these characters when reading the scheme.
+    let normalized = url.trim_matches(|c: char| c.is_ascii_control() || c == ' ')
+        .replace(['\t', '\n', '\r'], "");
+
+    let scheme = normalized.split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+
+    match scheme {
+        Some(scheme) => ALLOWED_SCHEMES.iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+            .then_some(url),
+        None => Some(url),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Creates GitHub style heading anchors, which are unique within a document.
+#[derive(Default)]
+struct Slugger {
+    seen: HashMap<String, usize>,
+}
+
+impl Slugger {
+    fn slug(&mut self, text: &str) -> String {
+        let slug = text.trim().to_lowercase().chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+                _ => None,
+            })
+            .collect::<String>();
+
+        let count = self.seen.entry(slug.clone()).or_insert(0);
+        let unique = if *count == 0 {
+            slug
+        } else {
+            format!("{}-{}", slug, count)
+        };
+        *count += 1;
+        unique
+    }
+}
+
+/// Serializes a render buffer to HTML, using the same CSS classes as the
+/// widgets the blocks are rendered as.
+pub fn to_html(buffer: &RenderBuffer) -> String {
+    let mut output = String::from("<div class=\"cmark-view\">\n");
+    write_blocks(&mut output, buffer, &mut Slugger::default());
+    output.push_str("</div>\n");
+    output
+}
+
+fn write_blocks(output: &mut String, buffer: &RenderBuffer, slugger: &mut Slugger) {
+    let mut alert: Option<AlertKind> = None;
+
+    for block in &buffer.blocks {
+        if block.alert != alert {
+            if alert.is_some() {
+                output.push_str("</div>\n");
+            }
+            if let Some(kind) = block.alert {
+                let _ = writeln!(output, "<div class=\"cmark-alert cmark-alert-{}\">", kind.name());
+            }
+            alert = block.alert;
+        }
+
+        write_block(output, buffer, block, slugger);
+    }
+
+    if alert.is_some() {
+        output.push_str("</div>\n");
+    }
+}
+
+fn write_block(output: &mut String, buffer: &RenderBuffer, block: &RenderBlock, slugger: &mut Slugger) {
+    let mut content = String::new();
+    match &block.node {
+        Node::Paragraph(paragraph) => {
+            content.push_str("<p class=\"cmark-label cmark-paragraph\">");
+            write_inline(&mut content, &InlineContent::from_nodes(&paragraph.children));
+            content.push_str("</p>");
+        },
+        Node::Heading(heading) => {
+            let inline = InlineContent::from_nodes(&heading.children);
+            let depth = heading.depth.clamp(1, 6);
+            let _ = write!(
+                content,
+                "<h{depth} id=\"{}\" class=\"cmark-label cmark-heading cmark-heading-{depth}\">",
+                escape(&slugger.slug(&inline.text)),
+            );
+            write_inline(&mut content, &inline);
+            let _ = write!(content, "</h{}>", depth);
+        },
+        Node::Code(code) => {
+            let language = code.lang.as_ref()
+                .map(|lang| format!(" class=\"language-{}\"", escape(lang)))
+                .unwrap_or_default();
+            let _ = write!(
+                content,
+                "<pre class=\"cmark-codeblock\"><code{}>{}</code></pre>",
+                language,
+                escape(&code.value),
+            );
+        },
+        Node::Table(table) => write_table(&mut content, table),
+        Node::ThematicBreak(_) => content.push_str("<hr class=\"cmark-thematic-break\">"),
+        Node::MdxJsxFlowElement(element) if html::is_element(&block.node, "img") => {
+            content.push_str("<img class=\"cmark-image\"");
+            for name in ["src", "alt", "title", "width", "height"] {
+                let value = html::element_attribute(element, name)
+                    .and_then(|value| if name == "src" { allowed_url(value) } else { Some(value) });
+                if let Some(value) = value {
+                    let _ = write!(content, " {}=\"{}\"", name, escape(value));
+                }
+            }
+            content.push('>');
+        },
+        Node::MdxJsxFlowElement(element) if html::is_element(&block.node, "details") => {
+            let open = html::element_attribute(element, "open").map_or("", |_| " open");
+            let summary = html::element_attribute(element, "summary").unwrap_or_default();
+            let _ = writeln!(
+                content,
+                "<details class=\"cmark-details\"{}><summary class=\"cmark-details-summary\">{}</summary>",
+                open,
+                escape(summary),
+            );
+
+            let mut nested = RenderBuffer {
+                options: buffer.options.clone(),
+                ..Default::default()
+            };
+            nested.set(&Node::Root(Root {
+                children: element.children.clone(),
+                position: None,
+            }));
+
+            content.push_str("<div class=\"cmark-view\">\n");
+            write_blocks(&mut content, &nested, slugger);
+            content.push_str("</div>\n</details>");
+        },
+        _ => return,
+    }
+
+    let mut indent = block.depth * DEPTH_MULTIPLIER;
+    if block.is_continuation {
+        indent += CONTINUATION_INDENT;
+    }
+
+    let style = if indent > 0 {
+        format!(" style=\"margin-left: {}px\"", indent)
+    } else {
+        String::new()
+    };
+
+    match &block.marker {
+        Some(marker) => {
+            let _ = writeln!(
+                output,
+                "<div class=\"marker-box\"{}><span class=\"marker-label\">{}</span>{}</div>",
+                style,
+                escape(&marker.label()),
+                content,
+            );
+        },
+        None if indent > 0 => {
+            let _ = writeln!(output, "<div{}>{}</div>", style, content);
+        },
+        None => {
+            output.push_str(&content);
+            output.push('\n');
+        },
+    }
+}
+
+fn write_table(output: &mut String, table: &Table) {
+    output.push_str("<table class=\"cmark-table\">");
+    for (r, row) in table.children.iter().enumerate() {
+        let cell_tag = if r == 0 { "th" } else { "td" };
+        output.push_str("<tr>");
+
+        for (c, cell) in row.children().into_iter().flatten().enumerate() {
+            let align = match table.align.get(c) {
+                Some(AlignKind::Left) => " style=\"text-align: left\"",
+                Some(AlignKind::Center) => " style=\"text-align: center\"",
+                Some(AlignKind::Right) => " style=\"text-align: right\"",
+                _ => "",
+            };
+
+            let _ = write!(output, "<{} class=\"cmark-table-cell\"{}>", cell_tag, align);
+            write_inline(output, &InlineContent::from_nodes(cell.children().map_or(&[], Vec::as_slice)));
+            let _ = write!(output, "</{}>", cell_tag);
+        }
+
+        output.push_str("</tr>");
+    }
+    output.push_str("</table>");
+}
+
+fn open_tag(style: &InlineStyle) -> String {
+    match style {
+        InlineStyle::Emphasis => "<em>".to_owned(),
+        InlineStyle::Strong => "<strong>".to_owned(),
+        InlineStyle::Delete => "<del>".to_owned(),
+        InlineStyle::Code => "<code>".to_owned(),
+        InlineStyle::Link(url) => match allowed_url(url) {
+            Some(url) => format!("<a href=\"{}\">", escape(url)),
+            None => "<a>".to_owned(),
+        },
+        InlineStyle::Underline => "<u>".to_owned(),
+        InlineStyle::Keyboard => "<kbd>".to_owned(),
+        InlineStyle::Subscript => "<sub>".to_owned(),
+        InlineStyle::Superscript => "<sup>".to_owned(),
+        InlineStyle::Mark => "<mark>".to_owned(),
+        InlineStyle::Small => "<small>".to_owned(),
+        InlineStyle::Abbreviation(Some(title)) => format!("<abbr title=\"{}\">", escape(title)),
+        InlineStyle::Abbreviation(None) => "<abbr>".to_owned(),
+        InlineStyle::Foreground(r, g, b) => format!(
+            "<span style=\"color: #{:02x}{:02x}{:02x}\">",
+            r >> 8,
+            g >> 8,
+            b >> 8,
+        ),
+    }
+}
+
+fn close_tag(style: &InlineStyle) -> &'static str {
+    match style {
+        InlineStyle::Emphasis => "</em>",
+        InlineStyle::Strong => "</strong>",
+        InlineStyle::Delete => "</del>",
+        InlineStyle::Code => "</code>",
+        InlineStyle::Link(_) => "</a>",
+        InlineStyle::Underline => "</u>",
+        InlineStyle::Keyboard => "</kbd>",
+        InlineStyle::Subscript => "</sub>",
+        InlineStyle::Superscript => "</sup>",
+        InlineStyle::Mark => "</mark>",
+        InlineStyle::Small => "</small>",
+        InlineStyle::Abbreviation(_) => "</abbr>",
+        InlineStyle::Foreground(..) => "</span>",
+    }
+}
+
+fn write_inline(output: &mut String, content: &InlineContent) {
+    output.push_str(&content.to_markup(
+        |style| (open_tag(style), close_tag(style).to_owned()),
+        |text| escape(text).replace('\n', "<br>\n"),
+    ));
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+use gtk4::{cairo, gdk, glib};
+
+use crate::images::ImageResolver;
+use crate::ir::RenderBuffer;
+use super::print::PrintLayout;
+
+const SNAPSHOT_PADDING: f64 = 12.0;
+const SNAPSHOT_BACKGROUND: (f64, f64, f64) = (1.0, 1.0, 1.0);
+/// The largest width or height of an image surface Cairo can create.
+const MAX_SURFACE_SIZE: i32 = 32767;
+
+/// Rasterizes a render buffer at a width in pixels, with Cairo's software
+/// rasterizer, so it works offscreen and without a GPU.
+///
+/// The document is laid out like it is for printing, as one long page, with
+/// images loaded through `images`. Documents taller than Cairo's limit of
+/// 32767 pixels are cropped at the bottom.
+pub(crate) fn render_surface(
+    buffer: &RenderBuffer,
+    images: Option<&ImageResolver>,
+    width: i32,
+) -> Result<cairo::ImageSurface, cairo::Error> {
+    let width = width.clamp(1, MAX_SURFACE_SIZE);
+
+    // The height is only known after layout, which needs a Pango context.
+    let measure = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)?;
+    let context = pangocairo::functions::create_context(&cairo::Context::new(&measure)?);
+    let layout = PrintLayout::new(
+        buffer,
+        &context,
+        images,
+        f64::from(width) - SNAPSHOT_PADDING * 2.0,
+        f64::INFINITY,
+    );
+
+    let height = (layout.page_height(0) + SNAPSHOT_PADDING * 2.0).ceil();
+    let height = height.clamp(1.0, f64::from(MAX_SURFACE_SIZE)) as i32;
+    let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    let cr = cairo::Context::new(&surface)?;
+
+    let (r, g, b) = SNAPSHOT_BACKGROUND;
+    cr.set_source_rgb(r, g, b);
+    cr.paint()?;
+
+    cr.translate(SNAPSHOT_PADDING, SNAPSHOT_PADDING);
+    layout.draw_page(&cr, 0);
+    drop(cr);
+
+    surface.flush();
+    Ok(surface)
+}
+
+/// Converts an image surface into a texture.
+pub(crate) fn surface_texture(mut surface: cairo::ImageSurface) -> Result<gdk::Texture, cairo::Error> {
+    let width = surface.width();
+    let height = surface.height();
+    let stride = surface.stride() as usize;
+
+    // Cairo stores pixels as native-endian 32-bit ARGB.
+    let format = if cfg!(target_endian = "little") {
+        gdk::MemoryFormat::B8g8r8a8Premultiplied
+    } else {
+        gdk::MemoryFormat::A8r8g8b8Premultiplied
+    };
+
+    let bytes = glib::Bytes::from(&*surface.data().map_err(|_| cairo::Error::SurfaceFinished)?);
+    Ok(gdk::MemoryTexture::new(width, height, format, &bytes, stride).into())
+}
+
+/// Writes a rasterized render buffer to a PNG file.
+///
+/// Images aren't loaded, only their alternative text is shown. Documents
+/// taller than Cairo's limit of 32767 pixels are cropped at the bottom.
+pub fn export_png(buffer: &RenderBuffer, width: i32, path: impl AsRef<Path>) -> Result<(), cairo::IoError> {
+    write_png(buffer, None, width, path)
+}
+
+/// Writes a rasterized render buffer to a PNG file, loading images through
+/// `images`.
+pub(crate) fn write_png(
+    buffer: &RenderBuffer,
+    images: Option<&ImageResolver>,
+    width: i32,
+    path: impl AsRef<Path>,
+) -> Result<(), cairo::IoError> {
+    let surface = render_surface(buffer, images, width)?;
+    let mut file = std::fs::File::create(path)?;
+    surface.write_to_png(&mut file)
+}
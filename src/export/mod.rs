@@ -0,0 +1,43 @@
+#[cfg(any(feature = "sourceview", feature = "syntect"))]
+mod highlight;
+mod html;
+mod image;
+mod print;
+mod text;
+
+use crate::ir::RenderBuffer;
+use crate::view::ParserFlavor;
+
+pub use html::to_html;
+pub use image::export_png;
+pub use print::{export_pdf, markdown_to_pdf};
+pub use text::{to_ansi, to_plain_text};
+pub(crate) use image::{render_surface, surface_texture, write_png};
+pub(crate) use print::{PrintLayout, write_pdf};
+
+/// Renders markdown with the default parser flavor and render options.
+fn render_markdown(markdown: &str) -> RenderBuffer {
+    let options = ParserFlavor::default()
+        .parse_options()
+        .unwrap_or_default();
+
+    // Parsing only fails for MDX, which the default flavor doesn't enable.
+    let mdast = markdown::to_mdast(markdown, &options)
+        .expect("markdown without MDX always parses");
+
+    let mut buffer = RenderBuffer::default();
+    buffer.set(&mdast);
+    buffer
+}
+
+/// Renders markdown to plain text wrapped to `width` columns, using the
+/// default parser flavor and render options. This doesn't need GTK.
+pub fn markdown_to_text(markdown: &str, width: usize) -> String {
+    to_plain_text(&render_markdown(markdown), width)
+}
+
+/// Renders markdown to ANSI-styled terminal text wrapped to `width` columns,
+/// using the default parser flavor and render options. This doesn't need GTK.
+pub fn markdown_to_ansi(markdown: &str, width: usize) -> String {
+    to_ansi(&render_markdown(markdown), width)
+}
@@ -0,0 +1,743 @@
+use std::path::Path;
+use std::rc::Rc;
+use gtk4::{cairo, gdk_pixbuf, gio, pango};
+use gtk4::gdk::prelude::*;
+use gtk4::pango::{AttrFloat, AttrInt};
+use markdown::mdast::{AlignKind, MdxJsxFlowElement, Node, Root, Table};
+
+use crate::html;
+use crate::images::{self, ImageResolver, ImageSource};
+use crate::inline::InlineContent;
+use crate::ir::{RenderBlock, RenderBuffer};
+#[cfg(any(feature = "sourceview", feature = "syntect"))]
+use super::highlight::highlight_code;
+
+/// A4, in points.
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const PAGE_MARGIN: f64 = 54.0;
+
+const BODY_FONT: &str = "Sans 10";
+const CODE_FONT: &str = "Monospace 8.5";
+const BLOCK_SPACING: f64 = 8.0;
+const HEADING_SPACING: f64 = 14.0;
+const DEPTH_MULTIPLIER: f64 = 16.0;
+const CONTINUATION_INDENT: f64 = 16.0;
+const MARKER_SPACING: f64 = 4.0;
+const CODE_PADDING: f64 = 6.0;
+const CELL_PADDING: f64 = 4.0;
+const ALERT_INDENT: f64 = 12.0;
+const ALERT_BAR_WIDTH: f64 = 3.0;
+const DETAILS_INDENT: f64 = 16.0;
+
+const TEXT_COLOR: (f64, f64, f64) = (0.0, 0.0, 0.0);
+const CODE_BACKGROUND: (f64, f64, f64) = (0.95, 0.95, 0.95);
+const HEADER_BACKGROUND: (f64, f64, f64) = (0.92, 0.92, 0.92);
+const RULE_COLOR: (f64, f64, f64) = (0.75, 0.75, 0.75);
+const ALERT_COLOR: (f64, f64, f64) = (0.55, 0.6, 0.7);
+
+const HEADING_SCALES: [(u8, f64); 6] = [
+    (1, 1.7),
+    (2, 1.5),
+    (3, 1.25),
+    (4, 1.1),
+    (5, 1.0),
+    (6, 0.9),
+];
+
+/// Something drawn within a row, positioned relative to the row's top left.
+#[derive(Debug, Clone)]
+enum Shape {
+    /// A band of a layout's lines, starting `offset` points into the layout.
+    Layout {
+        layout: pango::Layout,
+        x: f64,
+        y: f64,
+        offset: f64,
+        height: f64,
+    },
+    Rectangle {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: (f64, f64, f64),
+        fill: bool,
+    },
+    Image {
+        pixbuf: gdk_pixbuf::Pixbuf,
+        x: f64,
+        y: f64,
+        scale: f64,
+    },
+}
+
+impl Shape {
+    fn draw(&self, cr: &cairo::Context) {
+        match self {
+            Self::Layout { layout, x, y, offset, height } => {
+                let _ = cr.save();
+                let (left, _, right, _) = cr.clip_extents().unwrap_or_default();
+                cr.rectangle(left, *y, right - left, *height);
+                cr.clip();
+                let (r, g, b) = TEXT_COLOR;
+                cr.set_source_rgb(r, g, b);
+                cr.move_to(*x, y - offset);
+                pangocairo::functions::show_layout(cr, layout);
+                let _ = cr.restore();
+            },
+            Self::Rectangle { x, y, width, height, color, fill } => {
+                let (r, g, b) = *color;
+                cr.set_source_rgb(r, g, b);
+                cr.rectangle(*x, *y, *width, *height);
+                if *fill {
+                    let _ = cr.fill();
+                } else {
+                    cr.set_line_width(0.5);
+                    let _ = cr.stroke();
+                }
+            },
+            Self::Image { pixbuf, x, y, scale } => {
+                let _ = cr.save();
+                cr.translate(*x, *y);
+                cr.scale(*scale, *scale);
+                cr.set_source_pixbuf(pixbuf, 0.0, 0.0);
+                let _ = cr.paint();
+                let _ = cr.restore();
+            },
+        }
+    }
+}
+
+/// A slice of a block that is never split across pages.
+#[derive(Debug, Default)]
+struct Row {
+    shapes: Vec<Shape>,
+    height: f64,
+    /// The space above the row, unless it starts a page.
+    space_before: f64,
+    /// Moves the row to the next page along with the row after it, so
+    /// headings aren't left at the bottom of a page.
+    keep_with_next: bool,
+    /// A row repeated above this one when it starts a page, e.g. a table header.
+    header: Option<Rc<Row>>,
+}
+
+impl Row {
+    fn new(height: f64) -> Self {
+        Self {
+            height,
+            ..Default::default()
+        }
+    }
+
+    fn draw(&self, cr: &cairo::Context, y: f64) {
+        cr.translate(0.0, y);
+        for shape in &self.shapes {
+            shape.draw(cr);
+        }
+        cr.translate(0.0, -y);
+    }
+}
+
+/// Splits a render buffer into rows of a given width.
+struct RowBuilder<'a> {
+    context: &'a pango::Context,
+    images: Option<&'a ImageResolver>,
+    width: f64,
+    rows: Vec<Rc<Row>>,
+}
+
+impl RowBuilder<'_> {
+    fn push_blocks(&mut self, buffer: &RenderBuffer, x: f64) {
+        let mut previous_alert = None;
+        for block in &buffer.blocks {
+            let continues_alert = block.alert.is_some() && block.alert == previous_alert;
+            previous_alert = block.alert;
+            self.push_block(buffer, block, x, continues_alert);
+        }
+    }
+
+    fn push_block(&mut self, buffer: &RenderBuffer, block: &RenderBlock, x: f64, continues_alert: bool) {
+        let mut indent = x + block.depth as f64 * DEPTH_MULTIPLIER;
+        if block.is_continuation {
+            indent += CONTINUATION_INDENT;
+        }
+        if block.alert.is_some() {
+            indent += ALERT_INDENT;
+        }
+
+        let marker = block.marker.as_ref().map(|marker| {
+            let layout = self.layout(&plain_text(&marker.label()), BODY_FONT, indent, Vec::new());
+            let width = f64::from(layout.size().0) / f64::from(pango::SCALE);
+            (layout_shape(&layout, indent, 0.0, layout_height(&layout)), width)
+        });
+        if let Some((_, width)) = &marker {
+            indent += width + MARKER_SPACING;
+        }
+
+        let start = self.rows.len();
+        self.push_node(buffer, &block.node, indent);
+        let Some(first) = self.rows.get_mut(start).and_then(Rc::get_mut) else {
+            return;
+        };
+
+        if let Some((shape, _)) = marker {
+            first.shapes.push(shape);
+        }
+
+        if block.alert.is_some() {
+            for (i, row) in self.rows[start..].iter_mut().enumerate() {
+                let Some(row) = Rc::get_mut(row) else {
+                    continue;
+                };
+
+                // Joins the bar with the previous row of the same alert.
+                let extend = if i > 0 || continues_alert { row.space_before } else { 0.0 };
+                row.shapes.push(Shape::Rectangle {
+                    x,
+                    y: -extend,
+                    width: ALERT_BAR_WIDTH,
+                    height: row.height + extend,
+                    color: ALERT_COLOR,
+                    fill: true,
+                });
+            }
+        }
+    }
+
+    fn push_node(&mut self, buffer: &RenderBuffer, node: &Node, x: f64) {
+        match node {
+            Node::Paragraph(paragraph) => {
+                let layout = self.layout(&InlineContent::from_nodes(&paragraph.children), BODY_FONT, x, Vec::new());
+                self.push_lines(&layout, x, BLOCK_SPACING);
+            },
+            Node::Heading(heading) => {
+                let scale = HEADING_SCALES.iter()
+                    .find(|(depth, _)| *depth == heading.depth)
+                    .map_or(1.0, |(_, scale)| *scale);
+
+                let layout = self.layout(
+                    &InlineContent::from_nodes(&heading.children),
+                    BODY_FONT,
+                    x,
+                    vec![AttrFloat::new_scale(scale).into(), AttrInt::new_weight(pango::Weight::Bold).into()],
+                );
+
+                let mut row = layout_row(&layout, x, 0.0, layout_height(&layout));
+                row.space_before = HEADING_SPACING;
+                row.keep_with_next = true;
+                self.rows.push(Rc::new(row));
+            },
+            Node::Code(code) => self.push_code(&code.value, code.lang.as_deref(), x),
+            Node::Table(table) => self.push_table(table, x),
+            Node::ThematicBreak(_) => {
+                let mut row = Row::new(1.0);
+                row.space_before = BLOCK_SPACING;
+                row.shapes.push(Shape::Rectangle {
+                    x,
+                    y: 0.0,
+                    width: self.width - x,
+                    height: 1.0,
+                    color: RULE_COLOR,
+                    fill: true,
+                });
+                self.rows.push(Rc::new(row));
+            },
+            Node::MdxJsxFlowElement(element) if html::is_element(node, "img") => self.push_image(element, x),
+            Node::MdxJsxFlowElement(element) if html::is_element(node, "details") => {
+                let summary = html::element_attribute(element, "summary").unwrap_or_default();
+                let layout = self.layout(&plain_text(summary), BODY_FONT, x, vec![AttrInt::new_weight(pango::Weight::Bold).into()]);
+
+                let mut row = layout_row(&layout, x, 0.0, layout_height(&layout));
+                row.space_before = BLOCK_SPACING;
+                row.keep_with_next = true;
+                self.rows.push(Rc::new(row));
+
+                // Paper can't be expanded, so sections are always printed open.
+                let mut nested = RenderBuffer {
+                    options: buffer.options.clone(),
+                    ..Default::default()
+                };
+                nested.set(&Node::Root(Root {
+                    children: element.children.clone(),
+                    position: None,
+                }));
+                self.push_blocks(&nested, x + DETAILS_INDENT);
+            },
+            _ => {},
+        }
+    }
+
+    /// Pushes a row for each line of a layout, so it can break between pages.
+    fn push_lines(&mut self, layout: &pango::Layout, x: f64, space_before: f64) {
+        for (i, (top, bottom)) in line_bounds(layout).into_iter().enumerate() {
+            let mut row = layout_row(layout, x, top, bottom - top);
+            if i == 0 {
+                row.space_before = space_before;
+            }
+            self.rows.push(Rc::new(row));
+        }
+    }
+
+    fn push_code(&mut self, code: &str, lang: Option<&str>, x: f64) {
+        let code = code.trim_end_matches('\n');
+        let layout = self.layout(&plain_text(code), CODE_FONT, x, Vec::new());
+        layout.set_width(((self.width - x - CODE_PADDING * 2.0).max(1.0) * f64::from(pango::SCALE)) as i32);
+        layout.set_attributes(Some(&highlight_code(code, lang)));
+
+        let lines = line_bounds(&layout);
+        let last = lines.len().saturating_sub(1);
+        for (i, (top, bottom)) in lines.into_iter().enumerate() {
+            let padding_top = if i == 0 { CODE_PADDING } else { 0.0 };
+            let padding_bottom = if i == last { CODE_PADDING } else { 0.0 };
+            let mut row = Row::new(padding_top + bottom - top + padding_bottom);
+            if i == 0 {
+                row.space_before = BLOCK_SPACING;
+            }
+
+            row.shapes.push(Shape::Rectangle {
+                x,
+                y: 0.0,
+                width: self.width - x,
+                height: row.height,
+                color: CODE_BACKGROUND,
+                fill: true,
+            });
+            row.shapes.push(layout_shape(&layout, x + CODE_PADDING, top - padding_top, row.height));
+            self.rows.push(Rc::new(row));
+        }
+    }
+
+    /// Pushes a row per table row, repeating the header row when the table
+    /// continues on a new page.
+    fn push_table(&mut self, table: &Table, x: f64) {
+        let columns = table.children.iter()
+            .map(|row| row.children().map_or(0, Vec::len))
+            .max()
+            .unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+
+        let column_width = (self.width - x) / columns as f64;
+        let mut header = None;
+
+        for (r, table_row) in table.children.iter().enumerate() {
+            let cells = table_row.children().map_or(&[][..], Vec::as_slice);
+            let layouts = (0..columns)
+                .map(|c| {
+                    let content = cells.get(c)
+                        .and_then(Node::children)
+                        .map(|children| InlineContent::from_nodes(children))
+                        .unwrap_or_default();
+
+                    let weight = (r == 0).then(|| AttrInt::new_weight(pango::Weight::Bold).into());
+                    let layout = self.layout(&content, BODY_FONT, x, weight.into_iter().collect());
+                    layout.set_width(((column_width - CELL_PADDING * 2.0).max(1.0) * f64::from(pango::SCALE)) as i32);
+                    layout.set_alignment(match table.align.get(c) {
+                        Some(AlignKind::Center) => pango::Alignment::Center,
+                        Some(AlignKind::Right) => pango::Alignment::Right,
+                        _ => pango::Alignment::Left,
+                    });
+                    layout
+                })
+                .collect::<Vec<_>>();
+
+            let height = layouts.iter()
+                .map(layout_height)
+                .fold(0.0, f64::max) + CELL_PADDING * 2.0;
+
+            let mut row = Row::new(height);
+            if r == 0 {
+                row.shapes.push(Shape::Rectangle {
+                    x,
+                    y: 0.0,
+                    width: column_width * columns as f64,
+                    height,
+                    color: HEADER_BACKGROUND,
+                    fill: true,
+                });
+            }
+
+            for (c, layout) in layouts.iter().enumerate() {
+                let cell_x = x + column_width * c as f64;
+                row.shapes.push(Shape::Rectangle {
+                    x: cell_x,
+                    y: 0.0,
+                    width: column_width,
+                    height,
+                    color: RULE_COLOR,
+                    fill: false,
+                });
+                row.shapes.push(Shape::Layout {
+                    layout: layout.clone(),
+                    x: cell_x + CELL_PADDING,
+                    y: CELL_PADDING,
+                    offset: 0.0,
+                    height: height - CELL_PADDING * 2.0,
+                });
+            }
+
+            if r == 0 {
+                header = Some(Rc::new(Row {
+                    shapes: row.shapes.clone(),
+                    height,
+                    ..Default::default()
+                }));
+                row.space_before = BLOCK_SPACING;
+                row.keep_with_next = true;
+            } else {
+                row.header = header.clone();
+            }
+
+            self.rows.push(Rc::new(row));
+        }
+    }
+
+    /// Pushes an image, or its alternative text if it can't be loaded.
+    fn push_image(&mut self, element: &MdxJsxFlowElement, x: f64) {
+        let src = html::element_attribute(element, "src").unwrap_or_default();
+        let alt = html::element_attribute(element, "alt").unwrap_or_default();
+
+        // Remote images would block layout, so only their alt text is shown.
+        let source = images::resolve(src, self.images).filter(|source| !source.is_remote());
+        let Some(pixbuf) = source.and_then(load_pixbuf) else {
+            let layout = self.layout(&plain_text(alt), BODY_FONT, x, vec![AttrInt::new_style(pango::Style::Italic).into()]);
+            self.push_lines(&layout, x, BLOCK_SPACING);
+            return;
+        };
+
+        let natural_width = html::element_attribute(element, "width")
+            .and_then(|width| width.trim_end_matches("px").parse::<f64>().ok())
+            .unwrap_or(f64::from(pixbuf.width()));
+
+        let max_height = PAGE_HEIGHT - PAGE_MARGIN * 2.0;
+        let scale = (natural_width / f64::from(pixbuf.width()))
+            .min((self.width - x) / f64::from(pixbuf.width()))
+            .min(max_height / f64::from(pixbuf.height()));
+
+        let mut row = Row::new(f64::from(pixbuf.height()) * scale);
+        row.space_before = BLOCK_SPACING;
+        row.shapes.push(Shape::Image { pixbuf, x, y: 0.0, scale });
+        self.rows.push(Rc::new(row));
+    }
+
+    /// Creates a layout of inline content, with extra attributes applied to all of it.
+    fn layout(&self, content: &InlineContent, font: &str, x: f64, extra: Vec<pango::Attribute>) -> pango::Layout {
+        let layout = pango::Layout::new(self.context);
+        layout.set_font_description(Some(&pango::FontDescription::from_string(font)));
+        layout.set_wrap(pango::WrapMode::WordChar);
+        layout.set_width(((self.width - x).max(1.0) * f64::from(pango::SCALE)) as i32);
+        layout.set_text(&content.text);
+
+        let attrs = content.attr_list(true);
+        for attr in extra {
+            attrs.insert_before(attr);
+        }
+        layout.set_attributes(Some(&attrs));
+        layout
+    }
+}
+
+/// Paginates a render buffer for printing.
+#[derive(Debug)]
+pub(crate) struct PrintLayout {
+    pages: Vec<Vec<(Rc<Row>, f64)>>,
+}
+
+impl PrintLayout {
+    /// Lays out a render buffer on pages of the given size, in points,
+    /// loading images through `images`.
+    pub fn new(
+        buffer: &RenderBuffer,
+        context: &pango::Context,
+        images: Option<&ImageResolver>,
+        width: f64,
+        height: f64,
+    ) -> Self {
+        let mut builder = RowBuilder {
+            context,
+            images,
+            width,
+            rows: Vec::new(),
+        };
+        builder.push_blocks(buffer, 0.0);
+
+        let rows = builder.rows;
+        let mut pages = vec![Vec::new()];
+        let mut y = 0.0;
+
+        for (i, row) in rows.iter().enumerate() {
+            // Rows that must stay together are moved to a new page as a group.
+            let mut needed = row.space_before + row.height;
+            let mut j = i;
+            while rows[j].keep_with_next && j + 1 < rows.len() {
+                j += 1;
+                needed += rows[j].space_before + rows[j].height;
+            }
+
+            let page = pages.last_mut().expect("pages are never empty");
+            if !page.is_empty() && y + needed > height {
+                pages.push(Vec::new());
+                y = 0.0;
+            }
+
+            let page = pages.last_mut().expect("pages are never empty");
+            if page.is_empty() {
+                if let Some(header) = &row.header {
+                    page.push((header.clone(), 0.0));
+                    y = header.height;
+                }
+            } else {
+                y += row.space_before;
+            }
+
+            page.push((row.clone(), y));
+            y += row.height;
+        }
+
+        Self { pages }
+    }
+
+    pub fn n_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Gets the height of the content on a page.
+    pub fn page_height(&self, page: usize) -> f64 {
+        self.pages.get(page)
+            .and_then(|rows| rows.last())
+            .map_or(0.0, |(row, y)| y + row.height)
+    }
+
+    /// Draws a page, with the top left of the printable area at the origin.
+    pub fn draw_page(&self, cr: &cairo::Context, page: usize) {
+        for (row, y) in self.pages.get(page).into_iter().flatten() {
+            row.draw(cr, *y);
+        }
+    }
+}
+
+/// Writes a render buffer to a PDF, without needing a display.
+///
+/// Images aren't loaded, only their alternative text is shown.
+pub fn export_pdf(buffer: &RenderBuffer, path: impl AsRef<Path>) -> Result<(), cairo::Error> {
+    write_pdf(buffer, None, path)
+}
+
+/// Writes a render buffer to a PDF, loading images through `images`.
+pub(crate) fn write_pdf(
+    buffer: &RenderBuffer,
+    images: Option<&ImageResolver>,
+    path: impl AsRef<Path>,
+) -> Result<(), cairo::Error> {
+    let surface = cairo::PdfSurface::new(PAGE_WIDTH, PAGE_HEIGHT, path)?;
+    let cr = cairo::Context::new(&surface)?;
+
+    let context = pangocairo::functions::create_context(&cr);
+    pangocairo::functions::context_set_resolution(&context, 72.0);
+
+    let layout = PrintLayout::new(
+        buffer,
+        &context,
+        images,
+        PAGE_WIDTH - PAGE_MARGIN * 2.0,
+        PAGE_HEIGHT - PAGE_MARGIN * 2.0,
+    );
+
+    for page in 0..layout.n_pages() {
+        cr.save()?;
+        cr.translate(PAGE_MARGIN, PAGE_MARGIN);
+        layout.draw_page(&cr, page);
+        cr.restore()?;
+        cr.show_page()?;
+    }
+
+    surface.finish();
+    Ok(())
+}
+
+/// Renders markdown straight to a PDF file with A4 pages, using the default
+/// parser flavor and render options.
+///
+/// Unlike `MarkdownView::export_pdf`, this doesn't need a display, since it
+/// doesn't create any widgets.
+pub fn markdown_to_pdf(markdown: &str, path: impl AsRef<Path>) -> Result<(), cairo::Error> {
+    export_pdf(&super::render_markdown(markdown), path)
+}
+
+/// Leaves code uncolored, since there's no highlighter without GtkSourceView or syntect.
+#[cfg(not(any(feature = "sourceview", feature = "syntect")))]
+fn highlight_code(_code: &str, _lang: Option<&str>) -> pango::AttrList {
+    pango::AttrList::new()
+}
+
+/// Loads a local image synchronously, since pages are laid out all at once.
+fn load_pixbuf(source: ImageSource) -> Option<gdk_pixbuf::Pixbuf> {
+    let bytes = match source {
+        ImageSource::File(file) => file.load_bytes(None::<&gio::Cancellable>).ok()?.0,
+        ImageSource::Data(bytes) => bytes,
+    };
+
+    let stream = gio::MemoryInputStream::from_bytes(&bytes);
+    gdk_pixbuf::Pixbuf::from_stream(&stream, None::<&gio::Cancellable>).ok()
+}
+
+fn plain_text(text: &str) -> InlineContent {
+    InlineContent {
+        text: text.to_owned(),
+        ..Default::default()
+    }
+}
+
+/// Gets the top and bottom of each line of a layout, in points.
+fn line_bounds(layout: &pango::Layout) -> Vec<(f64, f64)> {
+    let scale = f64::from(pango::SCALE);
+    let mut iter = layout.iter();
+    let mut lines = Vec::new();
+
+    loop {
+        let (top, bottom) = iter.line_yrange();
+        lines.push((f64::from(top) / scale, f64::from(bottom) / scale));
+        if !iter.next_line() {
+            break;
+        }
+    }
+
+    lines
+}
+
+fn layout_height(layout: &pango::Layout) -> f64 {
+    f64::from(layout.size().1) / f64::from(pango::SCALE)
+}
+
+fn layout_shape(layout: &pango::Layout, x: f64, offset: f64, height: f64) -> Shape {
+    Shape::Layout {
+        layout: layout.clone(),
+        x,
+        y: 0.0,
+        offset,
+        height,
+    }
+}
+
+/// Creates a row showing a band of a layout's lines.
+fn layout_row(layout: &pango::Layout, x: f64, offset: f64, height: f64) -> Row {
+    let mut row = Row::new(height);
+    row.shapes.push(layout_shape(layout, x, offset, height));
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use flate2::read::ZlibDecoder;
+    use super::*;
+
+    /// Finds the first occurrence of `needle` in `haystack`.
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    /// Gets the text of a PDF along with its decompressed streams, since
+    /// page objects may be in compressed object streams.
+    fn pdf_text(pdf: &[u8]) -> String {
+        let mut text = String::from_utf8_lossy(pdf).into_owned();
+        let mut rest = pdf;
+        while let Some(start) = find(rest, b"stream") {
+            let data = &rest[start + b"stream".len()..];
+            let data = data.strip_prefix(b"\r").unwrap_or(data);
+            let data = data.strip_prefix(b"\n").unwrap_or(data);
+            let Some(end) = find(data, b"endstream") else {
+                break;
+            };
+
+            let mut decoded = Vec::new();
+            if ZlibDecoder::new(&data[..end]).read_to_end(&mut decoded).is_ok() {
+                text.push_str(&String::from_utf8_lossy(&decoded));
+            }
+            rest = &data[end + b"endstream".len()..];
+        }
+        text
+    }
+
+    /// Counts the `/Type /Page` objects of a PDF, which aren't `/Pages`.
+    fn count_pages(pdf: &[u8]) -> usize {
+        let text = pdf_text(pdf);
+        text.match_indices("/Type")
+            .filter(|(i, _)| {
+                let rest = text[i + "/Type".len()..].trim_start();
+                rest.strip_prefix("/Page")
+                    .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_alphanumeric()))
+            })
+            .count()
+    }
+
+    /// Lays out markdown on A4 pages like `markdown_to_pdf` does.
+    fn layout(markdown: &str) -> PrintLayout {
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1).unwrap();
+        let context = pangocairo::functions::create_context(&cairo::Context::new(&surface).unwrap());
+        pangocairo::functions::context_set_resolution(&context, 72.0);
+
+        PrintLayout::new(
+            &crate::export::render_markdown(markdown),
+            &context,
+            None,
+            PAGE_WIDTH - PAGE_MARGIN * 2.0,
+            PAGE_HEIGHT - PAGE_MARGIN * 2.0,
+        )
+    }
+
+    fn long_table(rows: usize) -> String {
+        let mut markdown = String::from("| # | Item |\n| --- | --- |\n");
+        for row in 0..rows {
+            markdown.push_str(&format!("| {} | Item number {} |\n", row, row));
+        }
+        markdown
+    }
+
+    #[test]
+    fn headings_stay_with_their_content() {
+        // Some amount of filler puts the heading at the bottom of the first page.
+        for filler in 0..60 {
+            let markdown = format!("{}## Heading\n\nContent after the heading.\n", "Filler paragraph.\n\n".repeat(filler));
+            let layout = layout(&markdown);
+
+            for page in &layout.pages[..layout.pages.len() - 1] {
+                let (last, _) = page.last().expect("pages aren't empty");
+                assert!(!last.keep_with_next, "a heading ends a page with {} filler paragraphs", filler);
+            }
+        }
+    }
+
+    #[test]
+    fn long_tables_repeat_their_header() {
+        let layout = layout(&long_table(200));
+        assert!(layout.n_pages() > 2);
+
+        for page in &layout.pages[1..] {
+            let (first, _) = page.first().expect("pages aren't empty");
+            assert!(Rc::ptr_eq(first, page[1].0.header.as_ref().expect("rows after the first page have a header")));
+        }
+    }
+
+    #[test]
+    fn writes_multi_page_pdf() {
+        let markdown = format!(
+            "# Report\n\n{}\n{}## Summary\n\nThe end.\n",
+            long_table(200),
+            "Filler paragraph.\n\n".repeat(30),
+        );
+        let expected_pages = layout(&markdown).n_pages();
+        assert!(expected_pages > 2);
+
+        let path = std::env::temp_dir().join(format!("gtk4cmark-test-{}.pdf", std::process::id()));
+        markdown_to_pdf(&markdown, &path).unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert_eq!(count_pages(&pdf), expected_pages);
+    }
+}
@@ -0,0 +1,499 @@
+use std::ops::Range;
+use markdown::mdast::{AlignKind, Node, Root, Table};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::html;
+use crate::inline::{InlineContent, InlineStyle};
+use crate::ir::{AlertKind, RenderBlock, RenderBuffer};
+
+const DEPTH_INDENT: usize = 2;
+const CONTINUATION_INDENT: usize = 2;
+const CODE_INDENT: usize = 4;
+const DETAILS_INDENT: usize = 2;
+const ALERT_BAR: &str = "│ ";
+const MIN_WIDTH: usize = 10;
+const MIN_COLUMN_WIDTH: usize = 3;
+
+const RESET: &str = "\x1b[0m";
+const CODE_COLOR: &str = "36";
+const BOLD: &str = "1";
+const DIM: &str = "2";
+
+/// Whether text output is styled with ANSI escape sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextFormat {
+    Plain,
+    Ansi,
+}
+
+impl AlertKind {
+    /// Gets the SGR color code alerts of this kind are shown in.
+    fn ansi_color(self) -> &'static str {
+        match self {
+            Self::Note => "34",
+            Self::Tip => "32",
+            Self::Important => "35",
+            Self::Warning => "33",
+            Self::Caution => "31",
+        }
+    }
+}
+
+/// Renders a render buffer to plain text, wrapped to `width` columns.
+pub fn to_plain_text(buffer: &RenderBuffer, width: usize) -> String {
+    let mut writer = TextWriter::new(TextFormat::Plain, width);
+    writer.write_buffer(buffer, 0);
+    writer.output
+}
+
+/// Renders a render buffer to text with ANSI colors and styles for
+/// terminals, wrapped to `width` columns.
+pub fn to_ansi(buffer: &RenderBuffer, width: usize) -> String {
+    let mut writer = TextWriter::new(TextFormat::Ansi, width);
+    writer.write_buffer(buffer, 0);
+    writer.output
+}
+
+struct TextWriter {
+    format: TextFormat,
+    width: usize,
+    output: String,
+}
+
+impl TextWriter {
+    fn new(format: TextFormat, width: usize) -> Self {
+        Self {
+            format,
+            width,
+            output: String::new(),
+        }
+    }
+
+    fn write_buffer(&mut self, buffer: &RenderBuffer, indent: usize) {
+        let mut previous: Option<&RenderBlock> = None;
+
+        for block in &buffer.blocks {
+            if let Some(previous) = previous {
+                // List items follow each other without a blank line.
+                let in_list = |block: &RenderBlock| block.marker.is_some() || block.is_continuation || block.depth > 0;
+                if !(block.marker.is_some() && in_list(previous)) {
+                    let alert = block.alert.filter(|_| previous.alert == block.alert);
+                    let prefix = self.prefix(indent, alert, 0);
+                    self.push_line(&prefix, "");
+                }
+            }
+
+            self.write_block(buffer, block, indent);
+            previous = Some(block);
+        }
+    }
+
+    /// Gets the text written before each line of a block.
+    fn prefix(&self, indent: usize, alert: Option<AlertKind>, block_indent: usize) -> String {
+        let mut prefix = " ".repeat(indent);
+        if let Some(alert) = alert {
+            prefix.push_str(&self.styled(ALERT_BAR, alert.ansi_color()));
+        }
+        prefix.push_str(&" ".repeat(block_indent));
+        prefix
+    }
+
+    fn write_block(&mut self, buffer: &RenderBuffer, block: &RenderBlock, indent: usize) {
+        let mut block_indent = block.depth * DEPTH_INDENT;
+        if block.is_continuation {
+            block_indent += CONTINUATION_INDENT;
+        }
+
+        let prefix = self.prefix(indent, block.alert, block_indent);
+        let marker = block.marker.as_ref().map(|marker| format!("{} ", marker.label()));
+        let marker_width = marker.as_ref().map_or(0, |marker| text_width(marker));
+
+        let available = self.width
+            .saturating_sub(indent + block_indent + marker_width + block.alert.map_or(0, |_| text_width(ALERT_BAR)))
+            .max(MIN_WIDTH);
+
+        let lines = self.block_lines(buffer, &block.node, available);
+        for (i, line) in lines.iter().enumerate() {
+            let marker = match &marker {
+                Some(marker) if i == 0 => marker.clone(),
+                _ => " ".repeat(marker_width),
+            };
+            self.push_line(&format!("{}{}", prefix, marker), line);
+        }
+    }
+
+    /// Renders a block's node to lines of at most `width` columns, without
+    /// the indentation in front of them.
+    fn block_lines(&self, buffer: &RenderBuffer, node: &Node, width: usize) -> Vec<String> {
+        match node {
+            Node::Paragraph(paragraph) => self.inline_lines(&InlineContent::from_nodes(&paragraph.children), width, None),
+            Node::Heading(heading) => {
+                let mut content = InlineContent::from_nodes(&heading.children);
+                if heading.depth > 2 {
+                    let hashes = format!("{} ", "#".repeat(heading.depth.into()));
+                    content.text.insert_str(0, &hashes);
+                    for span in &mut content.spans {
+                        span.range = span.range.start + hashes.len()..span.range.end + hashes.len();
+                    }
+                }
+
+                // The top two levels are underlined, like setext headings.
+                let mut lines = self.inline_lines(&content, width, Some(BOLD));
+                if heading.depth <= 2 {
+                    let underline = if heading.depth == 1 { "=" } else { "-" };
+                    let length = lines.iter().map(|line| text_width(&strip_ansi(line))).max().unwrap_or(0);
+                    lines.push(self.styled(&underline.repeat(length), DIM));
+                }
+                lines
+            },
+            Node::Code(code) => code.value.trim_end_matches('\n')
+                .lines()
+                .map(|line| format!("{}{}", " ".repeat(CODE_INDENT), self.styled(line, CODE_COLOR)))
+                .collect(),
+            Node::Table(table) => self.table_lines(table, width),
+            Node::ThematicBreak(_) => vec![self.styled(&"─".repeat(width), DIM)],
+            Node::MdxJsxFlowElement(element) if html::is_element(node, "img") => {
+                let alt = html::element_attribute(element, "alt").unwrap_or_default();
+                vec![self.styled(&format!("[image: {}]", alt), DIM)]
+            },
+            Node::MdxJsxFlowElement(element) if html::is_element(node, "details") => {
+                let summary = html::element_attribute(element, "summary").unwrap_or_default();
+                let mut lines = vec![self.styled(&format!("▼ {}", summary), BOLD)];
+
+                // Text can't be collapsed, so sections are always written open.
+                let mut nested = RenderBuffer {
+                    options: buffer.options.clone(),
+                    ..Default::default()
+                };
+                nested.set(&Node::Root(Root {
+                    children: element.children.clone(),
+                    position: None,
+                }));
+
+                let mut writer = Self::new(self.format, width);
+                writer.write_buffer(&nested, DETAILS_INDENT);
+                lines.extend(writer.output.lines().map(str::to_owned));
+                lines
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    /// Wraps inline content, optionally with a style applied to all of it.
+    fn inline_lines(&self, content: &InlineContent, width: usize, base: Option<&str>) -> Vec<String> {
+        let content = match self.format {
+            TextFormat::Plain => with_link_targets(content),
+            TextFormat::Ansi => content.clone(),
+        };
+
+        wrap(&content.text, width)
+            .into_iter()
+            .map(|range| self.styled_range(&content, range, base))
+            .collect()
+    }
+
+    /// Renders a table with box-drawing borders, wrapping cells to fit.
+    fn table_lines(&self, table: &Table, width: usize) -> Vec<String> {
+        let rows = table.children.iter()
+            .map(|row| row.children().map_or(&[][..], Vec::as_slice)
+                .iter()
+                .map(|cell| InlineContent::from_nodes(cell.children().map_or(&[][..], Vec::as_slice)))
+                .map(|content| match self.format {
+                    TextFormat::Plain => with_link_targets(&content),
+                    TextFormat::Ansi => content,
+                })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return Vec::new();
+        }
+
+        let mut widths = (0..columns)
+            .map(|c| rows.iter()
+                .filter_map(|row| row.get(c))
+                .flat_map(|content| content.text.lines().map(text_width).collect::<Vec<_>>())
+                .max()
+                .unwrap_or(0)
+                .max(MIN_COLUMN_WIDTH))
+            .collect::<Vec<_>>();
+
+        // Each column takes its padding and a border.
+        let budget = width.saturating_sub(columns * 3 + 1);
+        while widths.iter().sum::<usize>() > budget {
+            let Some(widest) = widths.iter_mut().max().filter(|widest| **widest > MIN_COLUMN_WIDTH) else {
+                break;
+            };
+            *widest -= 1;
+        }
+
+        let border = |left: &str, middle: &str, right: &str| {
+            let segments = widths.iter().map(|width| "─".repeat(width + 2)).collect::<Vec<_>>();
+            self.styled(&format!("{}{}{}", left, segments.join(middle), right), DIM)
+        };
+
+        let mut lines = vec![border("┌", "┬", "┐")];
+        for (r, row) in rows.iter().enumerate() {
+            if r == 1 {
+                lines.push(border("├", "┼", "┤"));
+            }
+
+            let base = (r == 0).then_some(BOLD);
+            let cells = (0..columns)
+                .map(|c| match row.get(c) {
+                    Some(content) => wrap(&content.text, widths[c])
+                        .into_iter()
+                        .map(|range| (self.styled_range(content, range.clone(), base), text_width(&content.text[range])))
+                        .collect(),
+                    None => Vec::new(),
+                })
+                .collect::<Vec<Vec<_>>>();
+
+            let height = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+            let separator = self.styled("│", DIM);
+            for i in 0..height {
+                let mut line = separator.clone();
+                for (c, cell) in cells.iter().enumerate() {
+                    let (text, length) = cell.get(i).cloned().unwrap_or_default();
+                    let padding = widths[c].saturating_sub(length);
+                    let (before, after) = match table.align.get(c) {
+                        Some(AlignKind::Right) => (padding, 0),
+                        Some(AlignKind::Center) => (padding / 2, padding - padding / 2),
+                        _ => (0, padding),
+                    };
+
+                    line.push_str(&format!(" {}{}{} ", " ".repeat(before), text, " ".repeat(after)));
+                    line.push_str(&separator);
+                }
+                lines.push(line);
+            }
+        }
+        lines.push(border("└", "┴", "┘"));
+        lines
+    }
+
+    /// Styles a byte range of inline content, resetting styles at its end.
+    fn styled_range(&self, content: &InlineContent, range: Range<usize>, base: Option<&str>) -> String {
+        let text = &content.text[range.clone()];
+        if self.format == TextFormat::Plain {
+            return text.to_owned();
+        }
+
+        let mut boundaries = content.spans.iter()
+            .flat_map(|span| [span.range.start, span.range.end])
+            .filter(|offset| range.contains(offset))
+            .chain([range.start, range.end])
+            .collect::<Vec<_>>();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut output = String::new();
+        let mut link: Option<&str> = None;
+        for segment in boundaries.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            let active = content.spans.iter()
+                .filter(|span| span.range.start <= start && end <= span.range.end)
+                .collect::<Vec<_>>();
+
+            let url = active.iter().rev().find_map(|span| match &span.style {
+                InlineStyle::Link(url) => Some(url.as_str()),
+                _ => None,
+            });
+            if url != link {
+                output.push_str(&hyperlink(url.unwrap_or_default()));
+                link = url;
+            }
+
+            let codes = base.into_iter()
+                .map(str::to_owned)
+                .chain(active.iter().filter_map(|span| ansi_codes(&span.style)))
+                .collect::<Vec<_>>();
+
+            output.push_str(RESET);
+            if !codes.is_empty() {
+                output.push_str(&format!("\x1b[{}m", codes.join(";")));
+            }
+            output.push_str(&content.text[start..end]);
+        }
+
+        if link.is_some() {
+            output.push_str(&hyperlink(""));
+        }
+        output.push_str(RESET);
+        output
+    }
+
+    /// Styles a whole string with an SGR code, when writing ANSI output.
+    fn styled(&self, text: &str, code: &str) -> String {
+        match self.format {
+            TextFormat::Plain => text.to_owned(),
+            TextFormat::Ansi => format!("\x1b[{}m{}{}", code, text, RESET),
+        }
+    }
+
+    fn push_line(&mut self, prefix: &str, line: &str) {
+        let line = format!("{}{}", prefix, line);
+        self.output.push_str(line.trim_end_matches(' '));
+        self.output.push('\n');
+    }
+}
+
+/// Gets the SGR codes for an inline style, if it can be shown in a terminal.
+fn ansi_codes(style: &InlineStyle) -> Option<String> {
+    let codes = match style {
+        InlineStyle::Emphasis => "3",
+        InlineStyle::Strong => BOLD,
+        InlineStyle::Delete => "9",
+        InlineStyle::Code | InlineStyle::Keyboard => CODE_COLOR,
+        InlineStyle::Link(_) => "4;34",
+        InlineStyle::Underline | InlineStyle::Abbreviation(_) => "4",
+        InlineStyle::Mark => "30;43",
+        InlineStyle::Small => DIM,
+        InlineStyle::Subscript | InlineStyle::Superscript => return None,
+        InlineStyle::Foreground(r, g, b) => return Some(format!("38;2;{};{};{}", r >> 8, g >> 8, b >> 8)),
+    };
+    Some(codes.to_owned())
+}
+
+/// Starts an OSC 8 hyperlink, or ends one when `url` is empty.
+fn hyperlink(url: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\", url)
+}
+
+/// Appends the targets of links to their text, since plain text can't link.
+///
+/// Links whose text is already the target, like autolinks, are left alone.
+fn with_link_targets(content: &InlineContent) -> InlineContent {
+    let mut links = content.spans.iter()
+        .filter_map(|span| match &span.style {
+            InlineStyle::Link(url) if content.text[span.range.clone()] != *url => Some((span.range.end, url)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    links.sort_by_key(|(end, _)| *end);
+
+    let mut text = String::with_capacity(content.text.len());
+    let mut offset = 0;
+    for (end, url) in links {
+        text.push_str(&content.text[offset..end]);
+        text.push_str(&format!(" ({})", url));
+        offset = end;
+    }
+    text.push_str(&content.text[offset..]);
+
+    InlineContent {
+        text,
+        spans: Vec::new(),
+    }
+}
+
+/// Wraps text at spaces to lines of at most `width` columns, splitting words
+/// that don't fit on a line of their own. Returns the byte range of each line.
+fn wrap(text: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for paragraph in text.split('\n') {
+        let mut start = offset;
+        let mut end = offset;
+        let mut line_width = 0;
+
+        for word in paragraph.split(' ') {
+            let word_start = offset;
+            let word_end = word_start + word.len();
+            offset = word_end + 1;
+
+            if line_width > 0 && line_width + 1 + text_width(word) > width {
+                lines.push(start..end);
+                start = word_start;
+                line_width = 0;
+            } else if line_width > 0 {
+                line_width += 1;
+            }
+
+            // Words longer than a line are split at character boundaries.
+            let mut word_start = word_start;
+            while line_width + text_width(&text[word_start..word_end]) > width {
+                let split = word_start + split_at_width(&text[word_start..word_end], width - line_width);
+                lines.push(start..split);
+                start = split;
+                word_start = split;
+                line_width = 0;
+            }
+
+            line_width += text_width(&text[word_start..word_end]);
+            end = word_end;
+        }
+
+        lines.push(start..end);
+    }
+
+    lines
+}
+
+/// Gets the number of columns text takes up in a terminal, with wide
+/// characters like CJK and emoji taking two.
+fn text_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Gets the byte index to split text at so the part before it fits in
+/// `width` columns. At least one character is kept, so splitting a word
+/// always makes progress, even when its first character is too wide.
+fn split_at_width(text: &str, width: usize) -> usize {
+    let mut columns = 0;
+    for (i, c) in text.char_indices() {
+        columns += c.width().unwrap_or(0);
+        if columns > width && i > 0 {
+            return i;
+        }
+    }
+    text.len()
+}
+
+/// Removes ANSI escape sequences from text.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() || c == '\\' {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::render_markdown;
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let text = "日本語のテキスト abc";
+        let lines = wrap(text, 6)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["日本語", "のテキ", "スト", "abc"]);
+    }
+
+    #[test]
+    fn tables_align_wide_characters() {
+        let text = to_plain_text(&render_markdown("| 名前 | x |\n| - | - |\n| a | 🦀 |"), 80);
+        assert_eq!(text.trim_end(), "\
+┌──────┬─────┐
+│ 名前 │ x   │
+├──────┼─────┤
+│ a    │ 🦀  │
+└──────┴─────┘");
+    }
+}
@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+use gtk4::prelude::*;
+
+use super::inline::{Highlight, InlineLabel};
+
+/// Options controlling how `MarkdownView::find` matches text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    /// Only match text with the same case as the query.
+    pub case_sensitive: bool,
+    /// Only match the query when it starts and ends at word boundaries.
+    pub whole_word: bool,
+}
+
+/// Finds every non-overlapping occurrence of `query` in `text`, returning byte ranges.
+pub fn find_in_text(text: &str, query: &str, options: &FindOptions) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+
+    let chars_eq = |a: char, b: char| if options.case_sensitive {
+        a == b
+    } else {
+        a.to_lowercase().eq(b.to_lowercase())
+    };
+
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut start = 0;
+    while start < text.len() {
+        let haystack = &text[start..];
+        let mut end = None;
+        let mut haystack_chars = haystack.char_indices();
+        let mut query_chars = query.chars();
+
+        loop {
+            match (query_chars.next(), haystack_chars.next()) {
+                (None, next) => {
+                    end = Some(start + next.map_or(haystack.len(), |(i, _)| i));
+                    break;
+                },
+                (Some(q), Some((_, c))) if chars_eq(q, c) => {},
+                _ => break,
+            }
+        }
+
+        let at_boundaries = |end: usize| !options.whole_word || (
+            !text[..start].chars().next_back().is_some_and(is_word_char)
+                && !text[end..].chars().next().is_some_and(is_word_char)
+        );
+
+        if let Some(end) = end && at_boundaries(end) {
+            matches.push(start..end);
+            start = end;
+        } else {
+            start += haystack.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    matches
+}
+
+/// A match within a label, as a byte range of its text.
+type LabelMatch = (InlineLabel, Range<usize>);
+
+/// Tracks find matches across a set of labels and highlights them.
+#[derive(Debug, Clone, Default)]
+pub struct LabelMatches {
+    matches: Rc<RefCell<Vec<LabelMatch>>>,
+}
+
+impl LabelMatches {
+    /// Highlights every match in the given labels, returning the number of matches.
+    pub fn find(&self, labels: &[InlineLabel], query: &str, options: &FindOptions) -> usize {
+        self.clear();
+
+        let mut matches = Vec::new();
+        for label in labels {
+            for range in find_in_text(&label.label.text(), query, options) {
+                matches.push((label.clone(), range));
+            }
+        }
+
+        self.matches.replace(matches);
+        self.highlight(None);
+        self.matches.borrow().len()
+    }
+
+    /// Highlights the `index`th match as the current one, returning its label.
+    pub fn select(&self, index: usize) -> Option<gtk4::Widget> {
+        let label = self.matches.borrow().get(index)?.0.label.clone();
+        self.highlight(Some(index));
+        Some(label.upcast())
+    }
+
+    /// Removes all highlighting.
+    pub fn clear(&self) {
+        for (label, _) in self.matches.take() {
+            label.set_highlights(Vec::new());
+        }
+    }
+
+    fn highlight(&self, current: Option<usize>) {
+        let matches = self.matches.borrow();
+        let mut labels: Vec<(&InlineLabel, Vec<Highlight>)> = Vec::new();
+
+        for (i, (label, range)) in matches.iter().enumerate() {
+            let highlight = (range.clone(), current == Some(i));
+            if let Some((_, highlights)) = labels.iter_mut().find(|(l, _)| l.label == label.label) {
+                highlights.push(highlight);
+            } else {
+                labels.push((label, vec![highlight]));
+            }
+        }
+
+        for (label, highlights) in labels {
+            label.set_highlights(highlights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CASE_SENSITIVE: FindOptions = FindOptions {
+        case_sensitive: true,
+        whole_word: false,
+    };
+
+    const WHOLE_WORD: FindOptions = FindOptions {
+        case_sensitive: false,
+        whole_word: true,
+    };
+
+    /// Finds matches as `(start, end)` byte offsets.
+    fn find(text: &str, query: &str, options: &FindOptions) -> Vec<(usize, usize)> {
+        find_in_text(text, query, options).into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    #[test]
+    fn empty_query_matches_nothing() {
+        assert!(find("text", "", &FindOptions::default()).is_empty());
+        assert!(find("", "", &FindOptions::default()).is_empty());
+        assert!(find("", "a", &FindOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn case_insensitive_by_default() {
+        assert_eq!(find("Rust rust RUST", "rust", &FindOptions::default()), [(0, 4), (5, 9), (10, 14)]);
+        assert_eq!(find("Rust rust RUST", "rust", &CASE_SENSITIVE), [(5, 9)]);
+        assert_eq!(find("Straße STRASSE", "straße", &FindOptions::default()), [(0, 7)]);
+        assert_eq!(find("ÉCOLE école", "école", &FindOptions::default()), [(0, 6), (7, 13)]);
+    }
+
+    #[test]
+    fn whole_words() {
+        let text = "cat concat cat_s cats (cat) cat";
+        assert_eq!(find(text, "cat", &WHOLE_WORD), [(0, 3), (23, 26), (28, 31)]);
+        assert_eq!(find("día días", "día", &WHOLE_WORD), [(0, 4)]);
+    }
+
+    #[test]
+    fn matches_never_overlap() {
+        assert_eq!(find("aaaa", "aa", &FindOptions::default()), [(0, 2), (2, 4)]);
+        assert_eq!(find("aaa", "aa", &FindOptions::default()), [(0, 2)]);
+        assert_eq!(find("abababa", "aba", &FindOptions::default()), [(0, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn byte_ranges_in_non_ascii_text() {
+        let text = "日本語のテキストと日本";
+        let matches = find(text, "日本", &FindOptions::default());
+        assert_eq!(matches, [(0, 6), (27, 33)]);
+        assert!(matches.iter().all(|(start, end)| &text[*start..*end] == "日本"));
+
+        assert_eq!(find("🦀 crab 🦀", "🦀", &FindOptions::default()), [(0, 4), (10, 14)]);
+    }
+}
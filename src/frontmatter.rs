@@ -0,0 +1,402 @@
+use gtk4::glib;
+use markdown::mdast::{AlignKind, Node, Strong, Table, TableCell, TableRow, Text};
+use markdown::unist::Position;
+
+/// How front matter is displayed.
+///
+/// It's shown by default, so a document that merely starts with a `---`
+/// block doesn't silently lose content.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkFrontMatterDisplay")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrontMatterDisplay {
+    /// Leaves front matter out of the rendered content.
+    Hide,
+    /// Shows the front matter's entries in a table above the content, or
+    /// its source if no entries could be read.
+    #[default]
+    Table,
+}
+
+/// The format front matter was written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontMatterFormat {
+    Yaml,
+    Toml,
+}
+
+/// Metadata from a document's front matter.
+///
+/// Lists are joined with commas, and nested maps and tables are flattened
+/// into dotted keys, so values are always plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontMatter {
+    pub format: FrontMatterFormat,
+    /// The front matter's entries as key/value pairs, in document order.
+    pub entries: Vec<(String, String)>,
+    /// The unparsed front matter.
+    pub raw: String,
+}
+
+impl FrontMatter {
+    /// Reads the front matter from a YAML or TOML node.
+    pub fn from_node(node: &Node) -> Option<Self> {
+        let (format, raw) = match node {
+            Node::Yaml(yaml) => (FrontMatterFormat::Yaml, &yaml.value),
+            Node::Toml(toml) => (FrontMatterFormat::Toml, &toml.value),
+            _ => return None,
+        };
+
+        let entries = match format {
+            FrontMatterFormat::Yaml => parse_yaml(raw),
+            FrontMatterFormat::Toml => parse_toml(raw),
+        };
+
+        Some(Self {
+            format,
+            entries,
+            raw: raw.clone(),
+        })
+    }
+
+    /// Gets the value of an entry by its key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(entry_key, _)| entry_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Creates a two column table of the entries, with keys in bold.
+    pub(crate) fn to_table(&self, position: Option<Position>) -> Node {
+        let text = |value: &str| Node::Text(Text {
+            value: value.to_owned(),
+            position: None,
+        });
+
+        let children = self.entries.iter()
+            .map(|(key, value)| Node::TableRow(TableRow {
+                children: vec![
+                    Node::TableCell(TableCell {
+                        children: vec![Node::Strong(Strong {
+                            children: vec![text(key)],
+                            position: None,
+                        })],
+                        position: None,
+                    }),
+                    Node::TableCell(TableCell {
+                        children: vec![text(value)],
+                        position: None,
+                    }),
+                ],
+                position: None,
+            }))
+            .collect();
+
+        Node::Table(Table {
+            children,
+            position,
+            align: vec![AlignKind::None; 2],
+        })
+    }
+}
+
+/// Removes a single pair of matching quotes around a value.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
+/// Finds the first occurrence of a character that isn't inside quotes or
+/// nested brackets, for which `matches` returns true.
+fn find_unquoted(text: &str, matches: impl Fn(&str, usize, char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0_usize;
+    for (i, c) in text.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {},
+            _ if matches(text, i, c) && depth == 0 => return Some(i),
+            ('[' | '{', None) => depth += 1,
+            (']' | '}', None) => depth = depth.saturating_sub(1),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Splits a list on the commas that aren't inside quotes or nested lists.
+fn split_items(mut items: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    while let Some(i) = find_unquoted(items, |_, _, c| c == ',') {
+        split.push(&items[..i]);
+        items = &items[i + 1..];
+    }
+    split.push(items);
+    split
+}
+
+/// Joins the items of an inline `[a, b]` list, or unquotes a scalar value.
+fn inline_value(value: &str) -> String {
+    let value = value.trim();
+    match value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+        Some(items) => split_items(items).into_iter()
+            .map(inline_value)
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => unquote(value).to_owned(),
+    }
+}
+
+/// Returns true if an inline list opened in the value is still open at its end.
+fn is_unclosed_list(value: &str) -> bool {
+    // The list ends where the closing bracket is back at depth 0.
+    let rest = value.strip_prefix('[');
+    rest.is_some_and(|rest| find_unquoted(rest, |_, _, c| c == ']').is_none())
+}
+
+/// Removes a trailing `#` comment that isn't inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) if i == 0 || line[..i].ends_with(char::is_whitespace) => return line[..i].trim_end(),
+            _ => {},
+        }
+    }
+    line
+}
+
+/// Splits a YAML `key: value` line at the first colon outside of quotes
+/// that ends the line or is followed by a space.
+fn split_yaml_entry(line: &str) -> Option<(&str, &str)> {
+    let i = find_unquoted(line, |text, i, c| {
+        c == ':' && text[i + 1..].chars().next().is_none_or(char::is_whitespace)
+    })?;
+    Some((&line[..i], &line[i + 1..]))
+}
+
+/// A YAML key without an inline value, whose value is on the lines after it.
+struct PendingYamlEntry {
+    key: String,
+    indent: usize,
+    /// The style of a `|` or `>` block scalar, if the value is one.
+    block_scalar: Option<char>,
+    /// The lines of a block scalar, or the items of a list.
+    items: Vec<String>,
+    /// Whether keys were nested under it, which makes it a map.
+    has_children: bool,
+}
+
+impl PendingYamlEntry {
+    fn finish(self, entries: &mut Vec<(String, String)>) {
+        if self.has_children && self.items.is_empty() {
+            return;
+        }
+
+        let separator = match self.block_scalar {
+            Some('|') => "\n",
+            Some(_) => " ",
+            None => ", ",
+        };
+        entries.push((self.key, self.items.join(separator)));
+    }
+}
+
+fn parse_yaml(raw: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    // The keys without inline values the following lines may be nested in.
+    let mut pending: Vec<PendingYamlEntry> = Vec::new();
+
+    for line in raw.lines() {
+        let indent = line.len() - line.trim_start().len();
+
+        if let Some(entry) = pending.last_mut()
+            && entry.block_scalar.is_some()
+            && (line.trim().is_empty() || indent > entry.indent)
+        {
+            entry.items.push(line.trim().to_owned());
+            continue;
+        }
+
+        let line = strip_comment(line).trim();
+        if line.is_empty() || line == "---" {
+            continue;
+        }
+
+        // List items may be indented as deeply as their key.
+        if let Some(item) = line.strip_prefix('-').filter(|item| item.is_empty() || item.starts_with(' ')) {
+            while pending.last().is_some_and(|entry| entry.indent > indent) {
+                pending.pop().expect("checked above").finish(&mut entries);
+            }
+            if let Some(entry) = pending.last_mut() {
+                let item = inline_value(item);
+                if !item.is_empty() {
+                    entry.items.push(item);
+                }
+            }
+            continue;
+        }
+
+        let Some((key, value)) = split_yaml_entry(line) else {
+            continue;
+        };
+
+        while pending.last().is_some_and(|entry| entry.indent >= indent) {
+            pending.pop().expect("checked above").finish(&mut entries);
+        }
+
+        let key = match pending.last_mut() {
+            Some(parent) => {
+                parent.has_children = true;
+                format!("{}.{}", parent.key, unquote(key))
+            },
+            None => unquote(key).to_owned(),
+        };
+
+        let value = value.trim();
+        let block_scalar = value.chars().next().filter(|c| matches!(c, '|' | '>'));
+        if value.is_empty() || block_scalar.is_some() {
+            pending.push(PendingYamlEntry {
+                key,
+                indent,
+                block_scalar,
+                items: Vec::new(),
+                has_children: false,
+            });
+        } else {
+            entries.push((key, inline_value(value)));
+        }
+    }
+
+    while let Some(entry) = pending.pop() {
+        entry.finish(&mut entries);
+    }
+    entries
+}
+
+fn parse_toml(raw: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut table = String::new();
+    // A key whose array continues on the following lines.
+    let mut open_array: Option<(String, String)> = None;
+
+    for line in raw.lines() {
+        let line = strip_comment(line).trim();
+
+        if let Some((key, mut value)) = open_array.take() {
+            value.push_str(line);
+            if is_unclosed_list(&value) {
+                open_array = Some((key, value));
+            } else {
+                entries.push((key, inline_value(&value)));
+            }
+            continue;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            table = name.trim_matches(['[', ']']).trim().to_owned();
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            let key = unquote(key);
+            let key = if table.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{}.{}", table, key)
+            };
+
+            let value = value.trim();
+            if is_unclosed_list(value) {
+                open_array = Some((key, value.to_owned()));
+            } else {
+                entries.push((key, inline_value(value)));
+            }
+        }
+    }
+
+    if let Some((key, value)) = open_array {
+        entries.push((key, inline_value(&value)));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(key, value)| ((*key).to_owned(), (*value).to_owned())).collect()
+    }
+
+    #[test]
+    fn yaml_scalars_and_comments() {
+        let raw = "title: \"Hello: world\" # greeting\nurl: https://example.com\ndraft: false";
+        assert_eq!(parse_yaml(raw), entries(&[
+            ("title", "Hello: world"),
+            ("url", "https://example.com"),
+            ("draft", "false"),
+        ]));
+    }
+
+    #[test]
+    fn yaml_lists() {
+        let raw = "tags: [a, \"b, c\", 'd']\nauthors:\n  - Ann\n  - Bob\nkeywords:\n- x\n- y";
+        assert_eq!(parse_yaml(raw), entries(&[
+            ("tags", "a, b, c, d"),
+            ("authors", "Ann, Bob"),
+            ("keywords", "x, y"),
+        ]));
+    }
+
+    #[test]
+    fn yaml_nested_maps() {
+        let raw = "author:\n  name: Ann\n  contact:\n    email: ann@example.com\ntitle: Post";
+        assert_eq!(parse_yaml(raw), entries(&[
+            ("author.name", "Ann"),
+            ("author.contact.email", "ann@example.com"),
+            ("title", "Post"),
+        ]));
+    }
+
+    #[test]
+    fn yaml_block_scalars() {
+        let raw = "literal: |\n  line one\n  # not a comment\nfolded: >\n  one\n  two\nafter: x";
+        assert_eq!(parse_yaml(raw), entries(&[
+            ("literal", "line one\n# not a comment"),
+            ("folded", "one two"),
+            ("after", "x"),
+        ]));
+    }
+
+    #[test]
+    fn toml_tables_and_arrays() {
+        let raw = "title = \"Post\"\ntags = [\"a, b\", 'c']\n\n[author]\nname = \"Ann\" # comment\n\n[[links]]\nurl = \"x\"";
+        assert_eq!(parse_toml(raw), entries(&[
+            ("title", "Post"),
+            ("tags", "a, b, c"),
+            ("author.name", "Ann"),
+            ("links.url", "x"),
+        ]));
+    }
+
+    #[test]
+    fn toml_multi_line_arrays() {
+        let raw = "numbers = [\n  1,\n  2, # two\n  [3, 4],\n]\nafter = true";
+        assert_eq!(parse_toml(raw), entries(&[
+            ("numbers", "1, 2, 3, 4"),
+            ("after", "true"),
+        ]));
+    }
+}
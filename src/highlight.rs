@@ -0,0 +1,319 @@
+#[cfg(feature = "syntect")]
+use std::ops::Range;
+#[cfg(feature = "syntect")]
+use std::sync::LazyLock;
+use gtk4::glib;
+#[cfg(feature = "syntect")]
+use gtk4::prelude::*;
+#[cfg(feature = "syntect")]
+use syntect::easy::HighlightLines;
+#[cfg(feature = "syntect")]
+use syntect::highlighting::{
+    Color, FontStyle, HighlightIterator, HighlightState, Highlighter, Style, Theme, ThemeSet,
+};
+#[cfg(feature = "syntect")]
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+#[cfg(feature = "syntect")]
+use syntect::util::LinesWithEndings;
+
+/// Common info string aliases, and the file extension both backends know
+/// the language by.
+#[cfg(any(feature = "sourceview", feature = "syntect"))]
+const LANGUAGE_ALIASES: [(&str, &str); 32] = [
+    ("rust", "rs"),
+    ("python", "py"),
+    ("python3", "py"),
+    ("py3", "py"),
+    ("javascript", "js"),
+    ("node", "js"),
+    ("jsx", "js"),
+    ("typescript", "ts"),
+    ("shell", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("console", "sh"),
+    ("yml", "yaml"),
+    ("c++", "cpp"),
+    ("cxx", "cpp"),
+    ("c#", "cs"),
+    ("csharp", "cs"),
+    ("golang", "go"),
+    ("ruby", "rb"),
+    ("markdown", "md"),
+    ("latex", "tex"),
+    ("patch", "diff"),
+    ("objc", "m"),
+    ("objective-c", "m"),
+    ("kotlin", "kt"),
+    ("haskell", "hs"),
+    ("perl", "pl"),
+    ("lisp", "lisp"),
+    ("clojure", "clj"),
+    ("erlang", "erl"),
+    ("make", "mk"),
+    ("makefile", "mk"),
+];
+
+/// The prefix of the names of the tags syntect highlighting is applied with.
+#[cfg(feature = "syntect")]
+const SYNTECT_TAG_PREFIX: &str = "cmark-syntect-";
+
+#[cfg(feature = "syntect")]
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+#[cfg(feature = "syntect")]
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Which syntax highlighter colors code blocks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkHighlightBackend")]
+pub enum HighlightBackend {
+    /// GtkSourceView when built with the `sourceview` feature, otherwise
+    /// syntect when built with the `syntect` feature.
+    #[default]
+    Auto,
+    /// GtkSourceView's language specs and style schemes, which have to be
+    /// installed on the system.
+    SourceView,
+    /// syntect's bundled grammars and themes.
+    Syntect,
+    /// Leaves code uncolored.
+    None,
+}
+
+impl HighlightBackend {
+    /// Resolves `Auto`, and backends that weren't built, to the backend
+    /// that's actually used.
+    #[cfg(any(feature = "sourceview", feature = "syntect"))]
+    pub(crate) fn resolve(self) -> Self {
+        match self {
+            Self::SourceView if cfg!(feature = "sourceview") => self,
+            Self::Syntect if cfg!(feature = "syntect") => self,
+            Self::None => self,
+            _ if cfg!(feature = "sourceview") => Self::SourceView,
+            _ if cfg!(feature = "syntect") => Self::Syntect,
+            _ => Self::None,
+        }
+    }
+}
+
+/// The color theme of code blocks, which exists in both backends.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkCodeTheme")]
+pub enum CodeTheme {
+    #[default]
+    Dark,
+    Light,
+    SolarizedDark,
+    SolarizedLight,
+}
+
+impl CodeTheme {
+    /// The id of the GtkSourceView style scheme.
+    #[cfg(feature = "sourceview")]
+    fn scheme_id(self) -> &'static str {
+        match self {
+            Self::Dark => "classic-dark",
+            Self::Light => "classic",
+            Self::SolarizedDark => "solarized-dark",
+            Self::SolarizedLight => "solarized-light",
+        }
+    }
+
+    /// The theme in syntect's default theme set.
+    #[cfg(feature = "syntect")]
+    fn syntect_theme(self) -> &'static Theme {
+        let name = match self {
+            Self::Dark => "base16-ocean.dark",
+            Self::Light => "InspiredGitHub",
+            Self::SolarizedDark => "Solarized (dark)",
+            Self::SolarizedLight => "Solarized (light)",
+        };
+
+        &THEMES.themes[name]
+    }
+}
+
+/// Gets the file extension of a code block's language, resolving aliases.
+#[cfg(any(feature = "sourceview", feature = "syntect"))]
+fn language_extension(lang: &str) -> String {
+    let lang = lang.to_lowercase();
+    LANGUAGE_ALIASES.iter()
+        .find(|(alias, _)| *alias == lang)
+        .map(|(_, extension)| (*extension).to_owned())
+        .unwrap_or(lang)
+}
+
+/// Finds the GtkSourceView language for a code block's language.
+#[cfg(feature = "sourceview")]
+pub(crate) fn source_language(lang: &str) -> Option<sourceview5::Language> {
+    let manager = sourceview5::LanguageManager::default();
+    manager.guess_language(Some(format!("file.{}", language_extension(lang))), None)
+        .or_else(|| manager.language(&lang.to_lowercase()))
+}
+
+/// Gets the GtkSourceView style scheme for a theme.
+#[cfg(feature = "sourceview")]
+pub(crate) fn source_scheme(theme: CodeTheme) -> Option<sourceview5::StyleScheme> {
+    sourceview5::StyleSchemeManager::default().scheme(theme.scheme_id())
+}
+
+/// Finds the bundled grammar for a code block's language, or plain text.
+#[cfg(feature = "syntect")]
+fn syntect_syntax(lang: Option<&str>) -> &'static SyntaxReference {
+    lang.and_then(|lang| {
+        SYNTAXES.find_syntax_by_extension(&language_extension(lang))
+            .or_else(|| SYNTAXES.find_syntax_by_token(lang))
+    })
+    .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
+}
+
+/// Highlights code with syntect, as byte ranges and their styles.
+#[cfg(feature = "syntect")]
+pub(crate) fn syntect_highlight(code: &str, lang: Option<&str>, theme: CodeTheme) -> Vec<(Range<usize>, Style)> {
+    let mut highlighter = HighlightLines::new(syntect_syntax(lang), theme.syntect_theme());
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+
+    for line in LinesWithEndings::from(code) {
+        let Ok(regions) = highlighter.highlight_line(line, &SYNTAXES) else {
+            break;
+        };
+
+        for (style, text) in regions {
+            ranges.push((offset..offset + text.len(), style));
+            offset += text.len();
+        }
+    }
+
+    ranges
+}
+
+/// The syntect parse and highlight states after each highlighted line of a
+/// buffer, so edits are only re-highlighted from the first changed line.
+#[cfg(feature = "syntect")]
+pub(crate) type LineCache = Vec<(ParseState, HighlightState)>;
+
+/// Colors a text buffer with syntect from `from_line` on, replacing any
+/// earlier syntect tags there and resuming from the cached state of the
+/// line before it.
+#[cfg(feature = "syntect")]
+pub(crate) fn apply_syntect_tags(
+    buffer: &gtk4::TextBuffer,
+    lang: Option<&str>,
+    theme: CodeTheme,
+    cache: &mut LineCache,
+    from_line: usize,
+) {
+    let from_line = from_line.min(cache.len());
+    cache.truncate(from_line);
+
+    let Some(mut start) = buffer.iter_at_line(from_line as i32) else {
+        return;
+    };
+    let end = buffer.end_iter();
+    clear_syntect_tags(buffer, &start, &end);
+
+    let theme = theme.syntect_theme();
+    if let Some(background) = theme.settings.background {
+        let tag = syntect_tag(buffer, &format!("bg-{}", color_string(background)), |tag| {
+            tag.set_paragraph_background_rgba(Some(&color_rgba(background)));
+        });
+        // Tags added later, like highlighted lines, are drawn over the background.
+        if tag.priority() != 0 {
+            tag.set_priority(0);
+        }
+        let (start, end) = buffer.bounds();
+        buffer.apply_tag(&tag, &start, &end);
+    }
+
+    let highlighter = Highlighter::new(theme);
+    let (mut highlight_state, mut parse_state) = match cache.last() {
+        Some((parse_state, highlight_state)) => (highlight_state.clone(), parse_state.clone()),
+        None => (
+            HighlightState::new(&highlighter, ScopeStack::new()),
+            ParseState::new(syntect_syntax(lang)),
+        ),
+    };
+
+    let code = buffer.text(&start, &end, true);
+    for line in LinesWithEndings::from(&code) {
+        let Ok(ops) = parse_state.parse_line(line, &SYNTAXES) else {
+            break;
+        };
+
+        for (style, text) in HighlightIterator::new(&mut highlight_state, &ops, line, &highlighter) {
+            let mut end = start;
+            end.forward_chars(text.chars().count() as i32);
+            apply_style(buffer, style, &start, &end);
+            start = end;
+        }
+
+        cache.push((parse_state.clone(), highlight_state.clone()));
+    }
+}
+
+/// Applies the tags for a syntect style to a range of a text buffer.
+#[cfg(feature = "syntect")]
+fn apply_style(buffer: &gtk4::TextBuffer, style: Style, start: &gtk4::TextIter, end: &gtk4::TextIter) {
+    let foreground = style.foreground;
+    let tag = syntect_tag(buffer, &format!("fg-{}", color_string(foreground)), |tag| {
+        tag.set_foreground_rgba(Some(&color_rgba(foreground)));
+    });
+    buffer.apply_tag(&tag, start, end);
+
+    if style.font_style.contains(FontStyle::BOLD) {
+        buffer.apply_tag(&syntect_tag(buffer, "bold", |tag| tag.set_weight(700)), start, end);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        let tag = syntect_tag(buffer, "italic", |tag| tag.set_style(gtk4::pango::Style::Italic));
+        buffer.apply_tag(&tag, start, end);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        let tag = syntect_tag(buffer, "underline", |tag| tag.set_underline(gtk4::pango::Underline::Single));
+        buffer.apply_tag(&tag, start, end);
+    }
+}
+
+/// Removes every syntect tag from a range of a text buffer.
+#[cfg(feature = "syntect")]
+pub(crate) fn clear_syntect_tags(buffer: &gtk4::TextBuffer, start: &gtk4::TextIter, end: &gtk4::TextIter) {
+    let mut tags = Vec::new();
+    buffer.tag_table().foreach(|tag| {
+        if tag.name().is_some_and(|name| name.starts_with(SYNTECT_TAG_PREFIX)) {
+            tags.push(tag.clone());
+        }
+    });
+
+    for tag in tags {
+        buffer.remove_tag(&tag, start, end);
+    }
+}
+
+/// Looks up a syntect tag in the buffer's tag table, creating it if needed.
+#[cfg(feature = "syntect")]
+fn syntect_tag(buffer: &gtk4::TextBuffer, name: &str, setup: impl FnOnce(&gtk4::TextTag)) -> gtk4::TextTag {
+    let tag_table = buffer.tag_table();
+    let name = format!("{}{}", SYNTECT_TAG_PREFIX, name);
+
+    tag_table.lookup(&name).unwrap_or_else(|| {
+        let tag = gtk4::TextTag::new(Some(&name));
+        setup(&tag);
+        tag_table.add(&tag);
+        tag
+    })
+}
+
+#[cfg(feature = "syntect")]
+fn color_string(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}{:02x}", color.r, color.g, color.b, color.a)
+}
+
+#[cfg(feature = "syntect")]
+fn color_rgba(color: Color) -> gtk4::gdk::RGBA {
+    gtk4::gdk::RGBA::new(
+        f32::from(color.r) / 255.0,
+        f32::from(color.g) / 255.0,
+        f32::from(color.b) / 255.0,
+        f32::from(color.a) / 255.0,
+    )
+}
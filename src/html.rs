@@ -0,0 +1,1042 @@
+use std::collections::VecDeque;
+use gtk4::glib;
+use markdown::mdast::{
+    AlignKind, AttributeContent, AttributeValue, Break, Heading, Html, Link,
+    MdxJsxAttribute, MdxJsxFlowElement, Node, Paragraph, Table, TableCell, TableRow, Text,
+    ThematicBreak,
+};
+use markdown::unist::Position;
+
+/// The attribute that marks elements created from HTML. It isn't a valid JSX
+/// attribute name, so MDX never produces it.
+const HTML_ELEMENT_MARKER: &str = "#cmark-html";
+
+/// Inline HTML tags that are mapped to Pango formatting, everything else is
+/// handled according to the `InlineHtmlPolicy`.
+pub const INLINE_TAGS: [&str; 17] = [
+    "br", "kbd", "sub", "sup", "u", "ins", "mark", "small", "abbr", "span",
+    "b", "strong", "i", "em", "s", "del", "code",
+];
+
+/// How inline HTML outside of the supported subset is displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkInlineHtmlPolicy")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InlineHtmlPolicy {
+    /// Shows the HTML as literal text.
+    #[default]
+    Show,
+    /// Removes the HTML, keeping any text between tags.
+    Strip,
+}
+
+/// How block-level HTML is displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkHtmlPolicy")]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HtmlPolicy {
+    /// Leaves HTML blocks out entirely.
+    #[default]
+    Ignore,
+    /// Shows HTML blocks as source in a code block.
+    Source,
+    /// Renders a sanitized subset of HTML: `<details>`, `<img>`, `<br>`,
+    /// `<hr>`, headings, links and simple tables, plus the supported inline
+    /// tags. Any other tags are dropped, keeping the text between them.
+    Render,
+}
+
+/// A single parsed HTML tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlTag {
+    /// The lowercase tag name.
+    pub name: String,
+    pub closing: bool,
+    pub self_closing: bool,
+    pub attributes: Vec<(String, String)>,
+    /// The byte length of the tag in the input.
+    pub len: usize,
+}
+
+impl HtmlTag {
+    /// Parses the HTML tag at the start of the input, ignoring leading whitespace.
+    pub fn parse(input: &str) -> Option<Self> {
+        let leading = input.len() - input.trim_start().len();
+        let input = input.trim_start();
+        let rest = input.strip_prefix('<')?;
+        let (closing, rest) = match rest.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+
+        let name_len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            return None;
+        }
+
+        let name = rest[..name_len].to_ascii_lowercase();
+        let mut chars = rest[name_len..].char_indices().peekable();
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+
+        loop {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+            let (i, c) = chars.next()?;
+            match c {
+                '>' => {
+                    let len = leading + input.len() - rest.len() + name_len + i + 1;
+                    return Some(Self { name, closing, self_closing, attributes, len });
+                },
+                '/' => self_closing = true,
+                _ => {
+                    let mut attr_name = c.to_string();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace() && !matches!(c, '=' | '>' | '/')) {
+                        attr_name.push(c);
+                    }
+
+                    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+                    let mut value = String::new();
+                    if chars.next_if(|(_, c)| *c == '=').is_some() {
+                        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+                        if let Some((_, quote)) = chars.next_if(|(_, c)| matches!(c, '"' | '\'')) {
+                            for (_, c) in chars.by_ref() {
+                                if c == quote {
+                                    break;
+                                }
+                                value.push(c);
+                            }
+                        } else {
+                            while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace() && *c != '>') {
+                                value.push(c);
+                            }
+                        }
+                    }
+
+                    attributes.push((attr_name.to_ascii_lowercase(), value));
+                },
+            }
+        }
+    }
+
+    /// Gets the value of an attribute by its lowercase name.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|(attr_name, _)| attr_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Gets the value of a CSS property from the tag's `style` attribute.
+    pub fn style_property(&self, property: &str) -> Option<&str> {
+        self.attribute("style")?
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case(property))
+            .map(|(_, value)| value.trim())
+    }
+}
+
+/// Returns true if the inline HTML is a tag in the supported subset.
+pub fn is_supported_inline(value: &str) -> bool {
+    HtmlTag::parse(value).is_some_and(|tag| INLINE_TAGS.contains(&tag.name.as_str()))
+}
+
+/// Applies the inline HTML policy to the inline HTML of a paragraph,
+/// heading or table.
+///
+/// Block HTML in other containers, like the body of a details section, is
+/// left alone, since it's rendered by its own block later.
+pub fn filter_inline_html(node: &mut Node, policy: InlineHtmlPolicy) {
+    match node {
+        Node::Paragraph(_) | Node::Heading(_) | Node::TableCell(_) => filter_phrasing_html(node, policy),
+        Node::Table(_) | Node::TableRow(_) => {
+            for child in node.children_mut().into_iter().flatten() {
+                filter_inline_html(child, policy);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Applies the inline HTML policy to every HTML node below phrasing content.
+fn filter_phrasing_html(node: &mut Node, policy: InlineHtmlPolicy) {
+    let Some(children) = node.children_mut() else {
+        return;
+    };
+
+    children.retain_mut(|child| {
+        if let Node::Html(html) = child
+            && !is_supported_inline(&html.value)
+        {
+            if policy == InlineHtmlPolicy::Strip {
+                return false;
+            }
+
+            let text = Text {
+                value: html.value.clone(),
+                position: html.position.clone(),
+            };
+            *child = Node::Text(text);
+        }
+
+        filter_phrasing_html(child, policy);
+        true
+    });
+}
+
+/// A piece of an HTML block.
+#[derive(Clone)]
+enum Token<'a> {
+    /// A tag, along with its source text.
+    Tag(HtmlTag, &'a str),
+    Text(&'a str),
+    Comment(&'a str),
+}
+
+impl Token<'_> {
+    /// Gets the source text of the token.
+    fn raw(&self) -> &str {
+        match self {
+            Token::Tag(_, raw) | Token::Text(raw) | Token::Comment(raw) => raw,
+        }
+    }
+
+    /// Returns true if the token is an opening or closing tag with the given name.
+    fn is_tag(&self, name: &str, closing: bool) -> bool {
+        matches!(self, Token::Tag(tag, _) if tag.name == name && tag.closing == closing)
+    }
+}
+
+/// Splits HTML into tags, comments and the text between them.
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+            rest = &rest[start..];
+        }
+
+        if rest.starts_with("<!--") {
+            let len = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            let (comment, remaining) = rest.split_at(len);
+            tokens.push(Token::Comment(comment));
+            rest = remaining;
+        } else if let Some(tag) = HtmlTag::parse(rest) {
+            let (raw, remaining) = rest.split_at(tag.len);
+            tokens.push(Token::Tag(tag, raw));
+            rest = remaining;
+        } else {
+            tokens.push(Token::Text(&rest[..1]));
+            rest = &rest[1..];
+        }
+    }
+
+    tokens
+}
+
+/// Decodes the common named and numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..end + 1];
+                let c = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    _ => name.strip_prefix("#x").or_else(|| name.strip_prefix("#X"))
+                        .map(|hex| u32::from_str_radix(hex, 16))
+                        .or_else(|| name.strip_prefix('#').map(str::parse))
+                        .and_then(Result::ok)
+                        .and_then(char::from_u32),
+                };
+                c.map(|c| (c, end + 2))
+            });
+
+        if let Some((c, len)) = entity {
+            decoded.push(c);
+            rest = &rest[len..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Returns the index of the tag closing the one at `start`, or the end of the tokens.
+fn matching_close(tokens: &[Token], start: usize, name: &str) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        if let Token::Tag(tag, _) = token
+            && tag.name == name
+            && !tag.self_closing
+        {
+            if !tag.closing {
+                depth += 1;
+            } else {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+        }
+    }
+    tokens.len()
+}
+
+/// Converts an HTML block to mdast block nodes, keeping only the subset
+/// described by `HtmlPolicy::Render`.
+///
+/// Every node is given the position of the whole HTML block, so source
+/// lookups resolve to it.
+pub fn html_block_to_nodes(value: &str, position: Option<&Position>) -> Vec<Node> {
+    let mut converter = BlockConverter::new(position);
+    converter.convert(&tokenize(value));
+    converter.finish()
+}
+
+/// Converts HTML tokens into block nodes.
+struct BlockConverter {
+    position: Option<Position>,
+    nodes: Vec<Node>,
+    /// The inline content of the paragraph or heading being built.
+    inline: Vec<Node>,
+    /// Open links, with the inline content preceding them.
+    links: Vec<(String, Vec<Node>)>,
+    heading: Option<u8>,
+}
+
+impl BlockConverter {
+    fn new(position: Option<&Position>) -> Self {
+        Self {
+            position: position.cloned(),
+            nodes: Vec::new(),
+            inline: Vec::new(),
+            links: Vec::new(),
+            heading: None,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Node> {
+        self.flush();
+        self.nodes
+    }
+
+    fn convert(&mut self, tokens: &[Token]) {
+        let mut i = 0;
+        while i < tokens.len() {
+            let (tag, raw) = match &tokens[i] {
+                Token::Text(text) => {
+                    self.push_text(text);
+                    i += 1;
+                    continue;
+                },
+                Token::Tag(tag, raw) => (tag, *raw),
+                Token::Comment(_) => {
+                    i += 1;
+                    continue;
+                },
+            };
+
+            match tag.name.as_str() {
+                "br" => self.inline.push(Node::Break(Break {
+                    position: self.position.clone(),
+                })),
+                "hr" => {
+                    self.flush();
+                    self.nodes.push(Node::ThematicBreak(ThematicBreak {
+                        position: self.position.clone(),
+                    }));
+                },
+                "img" => {
+                    self.flush();
+                    self.push_image(tag);
+                },
+                "table" if !tag.closing => {
+                    let end = matching_close(tokens, i, "table");
+                    self.flush();
+                    let table = self.table(&tokens[i + 1..end]);
+                    self.nodes.push(table);
+                    i = end;
+                },
+                "details" if !tag.closing => {
+                    let end = matching_close(tokens, i, "details");
+                    self.flush();
+                    let details = self.details(tag, &tokens[i + 1..end]);
+                    self.nodes.push(details);
+                    i = end;
+                },
+                "a" => if tag.closing {
+                    self.close_link();
+                } else if let Some(href) = tag.attribute("href") {
+                    let preceding = std::mem::take(&mut self.inline);
+                    self.links.push((decode_entities(href), preceding));
+                },
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    self.flush();
+                    self.heading = if tag.closing {
+                        None
+                    } else {
+                        tag.name[1..].parse().ok()
+                    };
+                },
+                "p" | "div" | "center" | "section" | "article" | "blockquote" | "pre"
+                    | "ul" | "ol" | "li" | "dl" | "dt" | "dd" | "summary" | "picture" => self.flush(),
+                name if INLINE_TAGS.contains(&name) => self.inline.push(Node::Html(Html {
+                    value: raw.to_owned(),
+                    position: self.position.clone(),
+                })),
+                _ => {},
+            }
+
+            i += 1;
+        }
+    }
+
+    /// Appends text, collapsing whitespace the way HTML does.
+    fn push_text(&mut self, text: &str) {
+        let text = decode_entities(text);
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            if c.is_ascii_whitespace() {
+                if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+            } else {
+                collapsed.push(c);
+            }
+        }
+
+        if let Some(Node::Text(last)) = self.inline.last_mut() {
+            if last.value.ends_with(' ') && collapsed.starts_with(' ') {
+                collapsed.remove(0);
+            }
+            last.value.push_str(&collapsed);
+        } else {
+            self.inline.push(Node::Text(Text {
+                value: collapsed,
+                position: self.position.clone(),
+            }));
+        }
+    }
+
+    fn push_image(&mut self, tag: &HtmlTag) {
+        if tag.attribute("src").is_none() {
+            return;
+        }
+
+        let attributes = ["src", "alt", "title", "width", "height"].into_iter()
+            .filter_map(|name| Some(AttributeContent::Property(MdxJsxAttribute {
+                name: name.to_owned(),
+                value: Some(AttributeValue::Literal(decode_entities(tag.attribute(name)?))),
+            })))
+            .collect();
+
+        self.nodes.push(html_element("img", attributes, Vec::new(), self.position.clone()));
+    }
+
+    fn close_link(&mut self) {
+        let Some((url, preceding)) = self.links.pop() else {
+            return;
+        };
+
+        let children = std::mem::replace(&mut self.inline, preceding);
+        self.inline.push(Node::Link(Link {
+            children,
+            position: self.position.clone(),
+            url,
+            title: None,
+        }));
+    }
+
+    /// Ends the current paragraph or heading, if it has any content.
+    fn flush(&mut self) {
+        while !self.links.is_empty() {
+            self.close_link();
+        }
+
+        let mut children = std::mem::take(&mut self.inline);
+        if let Some(Node::Text(first)) = children.first_mut() {
+            first.value = first.value.trim_start().to_owned();
+        }
+        if let Some(Node::Text(last)) = children.last_mut() {
+            last.value = last.value.trim_end().to_owned();
+        }
+        children.retain(|child| !matches!(child, Node::Text(text) if text.value.is_empty()));
+
+        let has_content = children.iter().any(|child| !matches!(child, Node::Html(_) | Node::Break(_)));
+        if !has_content {
+            return;
+        }
+
+        let position = self.position.clone();
+        self.nodes.push(match self.heading {
+            Some(depth) => Node::Heading(Heading { children, position, depth }),
+            None => Node::Paragraph(Paragraph { children, position }),
+        });
+    }
+
+    /// Converts the content of a `<table>` into a table node.
+    fn table(&self, tokens: &[Token]) -> Node {
+        let mut rows: Vec<Vec<Vec<Node>>> = Vec::new();
+        let mut i = 0;
+
+        while i < tokens.len() {
+            if let Token::Tag(tag, _) = &tokens[i] && !tag.closing {
+                match tag.name.as_str() {
+                    "tr" => rows.push(Vec::new()),
+                    "td" | "th" => {
+                        let end = matching_close(tokens, i, &tag.name);
+                        let mut cell = BlockConverter::new(self.position.as_ref());
+                        cell.convert(&tokens[i + 1..end]);
+
+                        let children = cell.finish().into_iter()
+                            .flat_map(|node| match node {
+                                Node::Paragraph(paragraph) => paragraph.children,
+                                Node::Heading(heading) => heading.children,
+                                _ => Vec::new(),
+                            })
+                            .collect();
+
+                        if rows.is_empty() {
+                            rows.push(Vec::new());
+                        }
+                        if let Some(row) = rows.last_mut() {
+                            row.push(children);
+                        }
+                        i = end;
+                    },
+                    _ => {},
+                }
+            }
+            i += 1;
+        }
+
+        // Table blocks expect every row to have the same number of cells.
+        rows.retain(|row| !row.is_empty());
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let children = rows.into_iter()
+            .map(|mut cells| {
+                cells.resize_with(columns, Vec::new);
+                Node::TableRow(TableRow {
+                    children: cells.into_iter()
+                        .map(|children| Node::TableCell(TableCell {
+                            children,
+                            position: self.position.clone(),
+                        }))
+                        .collect(),
+                    position: self.position.clone(),
+                })
+            })
+            .collect();
+
+        Node::Table(Table {
+            children,
+            position: self.position.clone(),
+            align: vec![AlignKind::None; columns],
+        })
+    }
+
+    /// Converts the content of a `<details>` into a details element, with
+    /// the text of its `<summary>` as an attribute.
+    fn details(&self, tag: &HtmlTag, tokens: &[Token]) -> Node {
+        let summary_start = tokens.iter().position(|token| token.is_tag("summary", false));
+
+        let (summary, body) = match summary_start {
+            Some(start) => {
+                let end = matching_close(tokens, start, "summary");
+                let body = tokens[..start].iter()
+                    .chain(tokens.get(end + 1..).unwrap_or_default())
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                (summary_text(&tokens[start + 1..end]), body)
+            },
+            None => (String::new(), tokens.to_vec()),
+        };
+
+        let mut converter = BlockConverter::new(self.position.as_ref());
+        converter.convert(&body);
+
+        details_element(
+            summary,
+            tag.attribute("open").is_some(),
+            converter.finish(),
+            self.position.clone(),
+        )
+    }
+}
+
+/// Gets the text of a `<summary>`, without any tags and with whitespace collapsed.
+fn summary_text(tokens: &[Token]) -> String {
+    let text = tokens.iter()
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(*text),
+            Token::Tag(..) | Token::Comment(_) => None,
+        })
+        .collect::<String>();
+
+    decode_entities(&text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Concatenates the source text of tokens.
+fn raw_text(tokens: &[Token]) -> String {
+    tokens.iter().map(Token::raw).collect()
+}
+
+/// Returns true if the token is only whitespace or a comment.
+fn is_blank(token: &Token) -> bool {
+    match token {
+        Token::Text(text) => text.trim().is_empty(),
+        Token::Comment(_) => true,
+        Token::Tag(..) => false,
+    }
+}
+
+/// The start of a `<details>` section that isn't closed within the same HTML node.
+struct DetailsOpener {
+    summary: Option<String>,
+    open: bool,
+    /// The HTML following the opening tag and summary.
+    rest: String,
+}
+
+impl DetailsOpener {
+    fn parse(value: &str) -> Option<Self> {
+        let tokens = tokenize(value);
+        let first = tokens.iter().position(|token| !is_blank(token))?;
+        let Token::Tag(tag, _) = &tokens[first] else {
+            return None;
+        };
+
+        let depth = tokens.iter()
+            .map(|token| if token.is_tag("details", false) {
+                1
+            } else if token.is_tag("details", true) {
+                -1
+            } else {
+                0
+            })
+            .sum::<i32>();
+
+        if !tokens[first].is_tag("details", false) || depth <= 0 {
+            return None;
+        }
+
+        let rest = raw_text(&tokens[first + 1..]);
+        let (summary, rest) = match leading_summary(&rest) {
+            Some((summary, rest)) => (Some(summary), rest),
+            None => (None, rest),
+        };
+
+        Some(Self {
+            summary,
+            open: tag.attribute("open").is_some(),
+            rest,
+        })
+    }
+}
+
+/// Splits a `<summary>` at the start of the HTML from the rest of it,
+/// returning the summary's text and the remaining HTML.
+fn leading_summary(value: &str) -> Option<(String, String)> {
+    let tokens = tokenize(value);
+    let start = tokens.iter().position(|token| !is_blank(token))?;
+    if !tokens[start].is_tag("summary", false) {
+        return None;
+    }
+
+    let end = matching_close(&tokens, start, "summary");
+    let rest = raw_text(tokens.get(end + 1..).unwrap_or_default());
+    Some((summary_text(&tokens[start + 1..end]), rest))
+}
+
+/// Finds the HTML node closing a `<details>` section opened before `nodes`,
+/// returning its index along with the HTML before and after the closing tag.
+fn find_details_close(nodes: &VecDeque<Node>) -> Option<(usize, String, String)> {
+    let mut depth = 1;
+    for (i, node) in nodes.iter().enumerate() {
+        let Node::Html(html) = node else {
+            continue;
+        };
+
+        let tokens = tokenize(&html.value);
+        for (j, token) in tokens.iter().enumerate() {
+            if token.is_tag("details", false) {
+                depth += 1;
+            } else if token.is_tag("details", true) {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((i, raw_text(&tokens[..j]), raw_text(&tokens[j + 1..])));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Groups `<details>` sections whose opening and closing tags are in
+/// separate HTML nodes, such as sections wrapping markdown content, into
+/// details elements containing the nodes between them.
+///
+/// A section that is never closed extends to the end of its parent.
+pub fn pair_details(node: &mut Node) {
+    if let Some(children) = node.children_mut() {
+        *children = pair_details_in(std::mem::take(children));
+    }
+}
+
+fn pair_details_in(nodes: Vec<Node>) -> Vec<Node> {
+    let mut nodes = VecDeque::from(nodes);
+    let mut paired = Vec::new();
+
+    while let Some(mut node) = nodes.pop_front() {
+        let opener = match &node {
+            Node::Html(html) => DetailsOpener::parse(&html.value),
+            _ => None,
+        };
+
+        let Some(mut opener) = opener else {
+            pair_details(&mut node);
+            paired.push(node);
+            continue;
+        };
+
+        // The summary may be in its own HTML node when separated by a blank line.
+        if opener.summary.is_none()
+            && opener.rest.trim().is_empty()
+            && let Some(Node::Html(next)) = nodes.front()
+            && let Some((summary, rest)) = leading_summary(&next.value)
+        {
+            opener.summary = Some(summary);
+            opener.rest = rest;
+            nodes.pop_front();
+        }
+
+        let (end, before, after) = find_details_close(&nodes)
+            .unwrap_or((nodes.len(), String::new(), String::new()));
+
+        let inner = nodes.drain(..end).collect::<Vec<_>>();
+        let closer = nodes.pop_front();
+        let last = closer.as_ref().or(inner.last()).unwrap_or(&node);
+        let position = match (node.position(), last.position()) {
+            (Some(start), Some(end)) => Some(Position {
+                start: start.start.clone(),
+                end: end.end.clone(),
+            }),
+            _ => None,
+        };
+
+        let mut children = Vec::new();
+        if !opener.rest.trim().is_empty() {
+            children.push(Node::Html(Html {
+                value: opener.rest,
+                position: node.position().cloned(),
+            }));
+        }
+
+        children.extend(pair_details_in(inner));
+
+        let closer_position = closer.as_ref().and_then(Node::position);
+        if !before.trim().is_empty() {
+            children.push(Node::Html(Html {
+                value: before,
+                position: closer_position.cloned(),
+            }));
+        }
+
+        if !after.trim().is_empty() {
+            nodes.push_front(Node::Html(Html {
+                value: after,
+                position: closer_position.cloned(),
+            }));
+        }
+
+        paired.push(details_element(
+            opener.summary.unwrap_or_default(),
+            opener.open,
+            children,
+            position,
+        ));
+    }
+
+    paired
+}
+
+/// Creates the element a `<details>` section is represented by in the IR.
+pub fn details_element(summary: String, open: bool, children: Vec<Node>, position: Option<Position>) -> Node {
+    let mut attributes = vec![AttributeContent::Property(MdxJsxAttribute {
+        name: "summary".to_owned(),
+        value: Some(AttributeValue::Literal(summary)),
+    })];
+
+    if open {
+        attributes.push(AttributeContent::Property(MdxJsxAttribute {
+            name: "open".to_owned(),
+            value: None,
+        }));
+    }
+
+    html_element("details", attributes, children, position)
+}
+
+/// Creates an element for rendered HTML, marked so it can't be confused
+/// with MDX JSX.
+fn html_element(name: &str, mut attributes: Vec<AttributeContent>, children: Vec<Node>, position: Option<Position>) -> Node {
+    attributes.push(AttributeContent::Property(MdxJsxAttribute {
+        name: HTML_ELEMENT_MARKER.to_owned(),
+        value: None,
+    }));
+
+    Node::MdxJsxFlowElement(MdxJsxFlowElement {
+        children,
+        position,
+        name: Some(name.to_owned()),
+        attributes,
+    })
+}
+
+/// Returns true if the node is an element created from rendered HTML.
+pub fn is_html_element(node: &Node) -> bool {
+    matches!(node, Node::MdxJsxFlowElement(element) if element_attribute(element, HTML_ELEMENT_MARKER).is_some())
+}
+
+/// Returns true if the node is an element created from HTML with the given tag name.
+pub fn is_element(node: &Node, name: &str) -> bool {
+    is_html_element(node) && matches!(node, Node::MdxJsxFlowElement(element) if element.name.as_deref() == Some(name))
+}
+
+/// Gets the value of an attribute of an element created from HTML.
+pub fn element_attribute<'a>(element: &'a MdxJsxFlowElement, name: &str) -> Option<&'a str> {
+    element.attributes.iter().find_map(|attribute| match attribute {
+        AttributeContent::Property(property) if property.name == name => match &property.value {
+            Some(AttributeValue::Literal(value)) => Some(value.as_str()),
+            _ => Some(""),
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use markdown::mdast::Blockquote;
+
+    fn html(value: &str) -> Node {
+        Node::Html(Html {
+            value: value.to_owned(),
+            position: None,
+        })
+    }
+
+    fn text(value: &str) -> Node {
+        Node::Text(Text {
+            value: value.to_owned(),
+            position: None,
+        })
+    }
+
+    #[test]
+    fn filters_inline_html_in_phrasing_content() {
+        let mut paragraph = Node::Paragraph(Paragraph {
+            children: vec![html("<b>"), html("<blink>"), text("a"), html("</blink>"), html("</b>")],
+            position: None,
+        });
+
+        filter_inline_html(&mut paragraph, InlineHtmlPolicy::Show);
+        assert_eq!(paragraph.children().map(Vec::as_slice), Some(&[
+            html("<b>"), text("<blink>"), text("a"), text("</blink>"), html("</b>"),
+        ][..]));
+
+        let mut paragraph = Node::Paragraph(Paragraph {
+            children: vec![html("<blink>"), text("a"), html("</blink>")],
+            position: None,
+        });
+        filter_inline_html(&mut paragraph, InlineHtmlPolicy::Strip);
+        assert_eq!(paragraph.children().map(Vec::as_slice), Some(&[text("a")][..]));
+    }
+
+    #[test]
+    fn leaves_block_html_in_containers_alone() {
+        let mut quote = Node::Blockquote(Blockquote {
+            children: vec![html("<img src=\"a.png\">")],
+            position: None,
+        });
+
+        let expected = quote.clone();
+        filter_inline_html(&mut quote, InlineHtmlPolicy::Strip);
+        assert_eq!(quote, expected);
+    }
+
+    fn tag_names(tokens: &[Token]) -> Vec<String> {
+        tokens.iter()
+            .map(|token| match token {
+                Token::Tag(tag, _) => format!("{}{}", if tag.closing { "/" } else { "" }, tag.name),
+                Token::Text(text) => format!("{text:?}"),
+                Token::Comment(comment) => format!("comment {comment:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_self_closing_tags() {
+        let tag = HtmlTag::parse("<br/>").expect("a tag");
+        assert_eq!((tag.name.as_str(), tag.self_closing, tag.len), ("br", true, 5));
+
+        let tag = HtmlTag::parse("  <img src=a.png />rest").expect("a tag");
+        assert!(tag.self_closing);
+        assert_eq!(tag.attribute("src"), Some("a.png"));
+        assert_eq!(tag.len, 19);
+    }
+
+    #[test]
+    fn parses_quoted_attributes() {
+        let input = r#"<a title="a > b" href='x"y' data-x=1 hidden>text"#;
+        let tag = HtmlTag::parse(input).expect("a tag");
+        assert_eq!(tag.attributes, [
+            ("title".to_owned(), "a > b".to_owned()),
+            ("href".to_owned(), "x\"y".to_owned()),
+            ("data-x".to_owned(), "1".to_owned()),
+            ("hidden".to_owned(), String::new()),
+        ]);
+        assert_eq!(&input[tag.len..], "text");
+    }
+
+    #[test]
+    fn parses_mixed_case() {
+        let tag = HtmlTag::parse("<DeTaiLs OPEN Class=X>").expect("a tag");
+        assert_eq!(tag.name, "details");
+        assert_eq!(tag.attribute("open"), Some(""));
+        assert_eq!(tag.attribute("class"), Some("X"));
+
+        let tag = HtmlTag::parse("</SUMMARY >").expect("a tag");
+        assert_eq!((tag.name.as_str(), tag.closing), ("summary", true));
+    }
+
+    #[test]
+    fn rejects_unclosed_and_invalid_tags() {
+        assert_eq!(HtmlTag::parse("<img src=\"a.png\""), None);
+        assert_eq!(HtmlTag::parse("<a title=\"unterminated>"), None);
+        assert_eq!(HtmlTag::parse("< b>"), None);
+        assert_eq!(HtmlTag::parse("a <b>"), None);
+    }
+
+    #[test]
+    fn tokenizes_comments_and_stray_brackets() {
+        let tokens = tokenize("a < b <!-- <b> --> <i>c</i> <!-- open");
+        assert_eq!(tag_names(&tokens), [
+            "\"a \"", "\"<\"", "\" b \"", "comment \"<!-- <b> -->\"", "\" \"",
+            "i", "\"c\"", "/i", "\" \"", "comment \"<!-- open\"",
+        ]);
+        assert_eq!(tokens.iter().map(Token::raw).collect::<String>(), "a < b <!-- <b> --> <i>c</i> <!-- open");
+    }
+
+    #[test]
+    fn converts_nested_details() {
+        let nodes = html_block_to_nodes(
+            "<details open><summary>Outer</summary><details><summary>Inner</summary>text</details>after</details>",
+            None,
+        );
+
+        let [outer] = nodes.as_slice() else {
+            panic!("expected one element, got {nodes:?}");
+        };
+        let Node::MdxJsxFlowElement(element) = outer else {
+            panic!("expected an element, got {outer:?}");
+        };
+        assert_eq!(element_attribute(element, "summary"), Some("Outer"));
+        assert_eq!(element_attribute(element, "open"), Some(""));
+
+        let [inner, after] = element.children.as_slice() else {
+            panic!("expected two children, got {:?}", element.children);
+        };
+        assert!(is_element(inner, "details"));
+        assert_eq!(after.to_string(), "after");
+    }
+
+    fn paired(nodes: Vec<Node>) -> Vec<Node> {
+        let mut root = Node::Root(markdown::mdast::Root {
+            children: nodes,
+            position: None,
+        });
+        pair_details(&mut root);
+        root.children().cloned().unwrap_or_default()
+    }
+
+    fn paragraph(value: &str) -> Node {
+        Node::Paragraph(Paragraph {
+            children: vec![text(value)],
+            position: None,
+        })
+    }
+
+    #[test]
+    fn pairs_details_across_nodes() {
+        let nodes = paired(vec![
+            html("<details>\n<summary>Outer</summary>"),
+            paragraph("a"),
+            html("<details><summary>Inner</summary>"),
+            paragraph("b"),
+            html("</details>"),
+            html("</details>"),
+            paragraph("c"),
+        ]);
+
+        let [outer, after] = nodes.as_slice() else {
+            panic!("expected two nodes, got {nodes:?}");
+        };
+        assert!(is_element(outer, "details"));
+        assert_eq!(after, &paragraph("c"));
+
+        let children = outer.children().cloned().unwrap_or_default();
+        let [a, inner] = children.as_slice() else {
+            panic!("expected two children, got {children:?}");
+        };
+        assert_eq!(a, &paragraph("a"));
+        assert!(is_element(inner, "details"));
+        assert_eq!(inner.children().map(Vec::as_slice), Some(&[paragraph("b")][..]));
+    }
+
+    #[test]
+    fn unbalanced_details() {
+        // A section that is never closed runs to the end of its parent.
+        let nodes = paired(vec![html("<details>"), paragraph("a"), paragraph("b")]);
+        let [details] = nodes.as_slice() else {
+            panic!("expected one node, got {nodes:?}");
+        };
+        assert!(is_element(details, "details"));
+        assert_eq!(details.children().map(Vec::len), Some(2));
+
+        // A closing tag without an opening one is left for the HTML policy.
+        let nodes = paired(vec![paragraph("a"), html("</details>")]);
+        assert_eq!(nodes, [paragraph("a"), html("</details>")]);
+
+        // Sections opened and closed in the same node are converted later.
+        let nodes = paired(vec![html("<details><summary>x</summary>y</details>")]);
+        assert_eq!(nodes, [html("<details><summary>x</summary>y</details>")]);
+    }
+
+}
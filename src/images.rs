@@ -0,0 +1,72 @@
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+
+/// Resolves the `src` of an `<img>` in rendered HTML to the file it's
+/// loaded from, or `None` to not load it.
+pub type ImageResolver = Rc<dyn Fn(&str) -> Option<gio::File>>;
+
+/// URI schemes images are loaded from. Remote schemes are only allowed for
+/// files an app-supplied resolver returns.
+const LOCAL_SCHEMES: [&str; 2] = ["file", "resource"];
+const REMOTE_SCHEMES: [&str; 2] = ["http", "https"];
+
+/// Where an image's data comes from.
+#[derive(Debug, Clone)]
+pub(crate) enum ImageSource {
+    File(gio::File),
+    /// The decoded content of a `data:` URI.
+    Data(glib::Bytes),
+}
+
+impl ImageSource {
+    /// Whether loading the image needs the network, which mustn't block.
+    pub fn is_remote(&self) -> bool {
+        match self {
+            Self::File(file) => !LOCAL_SCHEMES.iter().any(|scheme| file.has_uri_scheme(scheme)),
+            Self::Data(_) => false,
+        }
+    }
+}
+
+/// Resolves an `<img src>` to where it's loaded from.
+///
+/// Nothing is loaded without a resolver. With one, `data:` URIs are decoded
+/// directly and everything else goes through the resolver, whose result has
+/// to be a `file`, `resource`, `http` or `https` URI.
+pub(crate) fn resolve(src: &str, resolver: Option<&ImageResolver>) -> Option<ImageSource> {
+    let resolver = resolver?;
+    if let Some(data) = src.strip_prefix("data:") {
+        return decode_data_uri(data).map(ImageSource::Data);
+    }
+
+    let file = resolver(src)?;
+    let scheme = file.uri_scheme()?.to_ascii_lowercase();
+    (LOCAL_SCHEMES.contains(&scheme.as_str()) || REMOTE_SCHEMES.contains(&scheme.as_str()))
+        .then_some(ImageSource::File(file))
+}
+
+/// Creates a resolver that loads relative paths from within a directory.
+///
+/// URIs, absolute paths and paths leaving the directory with `..` aren't
+/// loaded.
+pub(crate) fn base_dir_resolver(dir: PathBuf) -> ImageResolver {
+    Rc::new(move |src| {
+        let path = Path::new(src);
+        let relative = !src.contains(':') && path.components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+        relative.then(|| gio::File::for_path(dir.join(path)))
+    })
+}
+
+/// Decodes the part of a base64 `data:` URI after the scheme.
+fn decode_data_uri(data: &str) -> Option<glib::Bytes> {
+    let (media_type, content) = data.split_once(',')?;
+    if !media_type.starts_with("image/") || !media_type.ends_with(";base64") {
+        return None;
+    }
+
+    Some(glib::Bytes::from_owned(glib::base64_decode(content)))
+}
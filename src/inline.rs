@@ -0,0 +1,601 @@
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::rc::Rc;
+use gtk4::glib;
+use gtk4::pango::{self, AttrColor, AttrFloat, AttrInt, AttrList, AttrString};
+use gtk4::prelude::*;
+use markdown::mdast::Node;
+
+use super::html::{self, HtmlTag};
+use super::view;
+
+pub(crate) const LINK_FOREGROUND: (u16, u16, u16) = (0x3535, 0x8484, 0xe4e4);
+const KEYBOARD_BACKGROUND: (u16, u16, u16) = (0x8080, 0x8080, 0x8080);
+const KEYBOARD_BACKGROUND_ALPHA: u16 = 0x4000;
+pub(crate) const MARK_BACKGROUND: (u16, u16, u16) = (0xffff, 0xf5f5, 0x9d9d);
+pub(crate) const ABBREVIATION_UNDERLINE: (u16, u16, u16) = (0x8888, 0x8888, 0x8888);
+const SCRIPT_SCALE: f64 = 0.75;
+const SUPERSCRIPT_RISE: i32 = 4 * pango::SCALE;
+const SUBSCRIPT_RISE: i32 = -2 * pango::SCALE;
+const SMALL_SCALE: f64 = 0.83;
+const MATCH_BACKGROUND: (u16, u16, u16) = (0xf6f6, 0xd3d3, 0x2d2d);
+const CURRENT_MATCH_BACKGROUND: (u16, u16, u16) = (0xffff, 0x7878, 0x0000);
+const MATCH_FOREGROUND: (u16, u16, u16) = (0x0000, 0x0000, 0x0000);
+
+/// A formatting style applied to a span of inline text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineStyle {
+    Emphasis,
+    Strong,
+    Delete,
+    Code,
+    Link(String),
+    Underline,
+    Keyboard,
+    Subscript,
+    Superscript,
+    Mark,
+    Small,
+    /// An abbreviation, with its expansion from the `title` attribute.
+    Abbreviation(Option<String>),
+    Foreground(u16, u16, u16),
+}
+
+impl InlineStyle {
+    /// Gets the style for an opening inline HTML tag in the supported subset.
+    fn from_html_tag(tag: &HtmlTag) -> Option<Self> {
+        match tag.name.as_str() {
+            "b" | "strong" => Some(Self::Strong),
+            "i" | "em" => Some(Self::Emphasis),
+            "s" | "del" => Some(Self::Delete),
+            "code" => Some(Self::Code),
+            "u" | "ins" => Some(Self::Underline),
+            "kbd" => Some(Self::Keyboard),
+            "sub" => Some(Self::Subscript),
+            "sup" => Some(Self::Superscript),
+            "mark" => Some(Self::Mark),
+            "small" => Some(Self::Small),
+            "abbr" => Some(Self::Abbreviation(tag.attribute("title").map(str::to_owned))),
+            "span" => {
+                let color = pango::Color::parse(tag.style_property("color")?).ok()?;
+                Some(Self::Foreground(color.red(), color.green(), color.blue()))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A style applied to a byte range of inline text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineSpan {
+    pub range: Range<usize>,
+    pub style: InlineStyle,
+}
+
+/// Plain text with styled spans, built from inline markdown nodes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InlineContent {
+    pub text: String,
+    pub spans: Vec<InlineSpan>,
+}
+
+impl InlineContent {
+    /// Builds the content of a sequence of inline nodes.
+    pub fn from_nodes(nodes: &[Node]) -> Self {
+        let mut content = Self::default();
+        content.push_nodes(nodes);
+        content
+    }
+
+    /// Converts the styled spans into Pango attributes, leaving links
+    /// unstyled unless `style_links` is set, e.g. for labels that style
+    /// their links from CSS.
+    pub fn attr_list(&self, style_links: bool) -> AttrList {
+        let attrs = AttrList::new();
+        for span in &self.spans {
+            match &span.style {
+                InlineStyle::Emphasis => attrs.insert(with_range(AttrInt::new_style(pango::Style::Italic), &span.range)),
+                InlineStyle::Strong => attrs.insert(with_range(AttrInt::new_weight(pango::Weight::Bold), &span.range)),
+                InlineStyle::Delete => attrs.insert(with_range(AttrInt::new_strikethrough(true), &span.range)),
+                InlineStyle::Code => attrs.insert(with_range(AttrString::new_family("monospace"), &span.range)),
+                InlineStyle::Link(_) if !style_links => {},
+                InlineStyle::Link(_) => {
+                    let (r, g, b) = LINK_FOREGROUND;
+                    attrs.insert(with_range(AttrInt::new_underline(pango::Underline::Single), &span.range));
+                    attrs.insert(with_range(AttrColor::new_foreground(r, g, b), &span.range));
+                },
+                InlineStyle::Underline => attrs.insert(with_range(AttrInt::new_underline(pango::Underline::Single), &span.range)),
+                InlineStyle::Keyboard => {
+                    let (r, g, b) = KEYBOARD_BACKGROUND;
+                    attrs.insert(with_range(AttrString::new_family("monospace"), &span.range));
+                    attrs.insert(with_range(AttrColor::new_background(r, g, b), &span.range));
+                    attrs.insert(with_range(AttrInt::new_background_alpha(KEYBOARD_BACKGROUND_ALPHA), &span.range));
+                },
+                InlineStyle::Subscript => {
+                    attrs.insert(with_range(AttrFloat::new_scale(SCRIPT_SCALE), &span.range));
+                    attrs.insert(with_range(AttrInt::new_rise(SUBSCRIPT_RISE), &span.range));
+                },
+                InlineStyle::Superscript => {
+                    attrs.insert(with_range(AttrFloat::new_scale(SCRIPT_SCALE), &span.range));
+                    attrs.insert(with_range(AttrInt::new_rise(SUPERSCRIPT_RISE), &span.range));
+                },
+                InlineStyle::Mark => {
+                    let (r, g, b) = MARK_BACKGROUND;
+                    attrs.insert(with_range(AttrColor::new_background(r, g, b), &span.range));
+                    let (r, g, b) = MATCH_FOREGROUND;
+                    attrs.insert(with_range(AttrColor::new_foreground(r, g, b), &span.range));
+                },
+                InlineStyle::Small => attrs.insert(with_range(AttrFloat::new_scale(SMALL_SCALE), &span.range)),
+                InlineStyle::Abbreviation(_) => {
+                    let (r, g, b) = ABBREVIATION_UNDERLINE;
+                    attrs.insert(with_range(AttrInt::new_underline(pango::Underline::Single), &span.range));
+                    attrs.insert(with_range(AttrColor::new_underline_color(r, g, b), &span.range));
+                },
+                InlineStyle::Foreground(r, g, b) => attrs.insert(with_range(AttrColor::new_foreground(*r, *g, *b), &span.range)),
+            }
+        }
+        attrs
+    }
+
+    /// Appends text, styling all of it if a style is given.
+    pub fn push_text(&mut self, text: &str, style: Option<InlineStyle>) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        if let Some(style) = style {
+            self.push_span(start, style);
+        }
+    }
+
+    /// Appends other content along with its spans.
+    pub fn append(&mut self, other: &InlineContent) {
+        let offset = self.text.len();
+        self.text.push_str(&other.text);
+        self.spans.extend(other.spans.iter().map(|span| InlineSpan {
+            range: span.range.start + offset..span.range.end + offset,
+            style: span.style.clone(),
+        }));
+    }
+
+    /// Converts the content to label markup with only its links as `<a>`
+    /// tags, so the label can style and activate them, and everything else
+    /// left to `attr_list`.
+    pub fn link_markup(&self) -> String {
+        let mut links = self.spans.iter()
+            .filter_map(|span| match &span.style {
+                InlineStyle::Link(url) => Some((span.range.clone(), url)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        links.sort_by_key(|(range, _)| range.start);
+
+        let mut markup = String::new();
+        let mut offset = 0;
+        for (range, url) in links {
+            // Links can't contain links, but an HTML link could overlap one.
+            if range.start < offset {
+                continue;
+            }
+
+            markup.push_str(&glib::markup_escape_text(&self.text[offset..range.start]));
+            markup.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                glib::markup_escape_text(url),
+                glib::markup_escape_text(&self.text[range.clone()]),
+            ));
+            offset = range.end;
+        }
+
+        markup.push_str(&glib::markup_escape_text(&self.text[offset..]));
+        markup
+    }
+
+    /// Converts the content to markup, wrapping each span in the opening and
+    /// closing tags `tags` gives for its style and escaping text with `escape`.
+    ///
+    /// Spans are split where they overlap, so the tags stay properly nested.
+    pub fn to_markup(
+        &self,
+        tags: impl Fn(&InlineStyle) -> (String, String),
+        escape: impl Fn(&str) -> String,
+    ) -> String {
+        let mut boundaries = self.spans.iter()
+            .flat_map(|span| [span.range.start, span.range.end])
+            .chain([0, self.text.len()])
+            .filter(|offset| self.text.is_char_boundary(*offset))
+            .collect::<Vec<_>>();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut output = String::new();
+        let mut open: Vec<usize> = Vec::new();
+        for segment in boundaries.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+
+            // Outer spans first, so longer spans enclose shorter ones.
+            let mut active = (0..self.spans.len())
+                .filter(|i| {
+                    let range = &self.spans[*i].range;
+                    range.start <= start && end <= range.end
+                })
+                .collect::<Vec<_>>();
+            active.sort_by_key(|i| (self.spans[*i].range.start, std::cmp::Reverse(self.spans[*i].range.end)));
+
+            let common = open.iter().zip(&active).take_while(|(a, b)| a == b).count();
+            for i in open.drain(common..).rev() {
+                output.push_str(&tags(&self.spans[i].style).1);
+            }
+            for i in &active[common..] {
+                output.push_str(&tags(&self.spans[*i].style).0);
+                open.push(*i);
+            }
+
+            output.push_str(&escape(&self.text[start..end]));
+        }
+
+        for i in open.into_iter().rev() {
+            output.push_str(&tags(&self.spans[i].style).1);
+        }
+        output
+    }
+
+    /// Gets the expansion of the innermost abbreviation at a byte index, if any.
+    pub fn abbreviation_at(&self, index: usize) -> Option<&str> {
+        self.spans.iter().rev().find_map(|span| match &span.style {
+            InlineStyle::Abbreviation(Some(title)) if span.range.contains(&index) => Some(title.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Pushes sibling nodes, pairing up supported inline HTML tags among them.
+    ///
+    /// Tags left open at the end of the siblings are closed there, and
+    /// closing tags without a matching opening tag are dropped.
+    fn push_nodes(&mut self, nodes: &[Node]) {
+        let mut open_tags: Vec<(String, Option<InlineStyle>, usize)> = Vec::new();
+
+        for node in nodes {
+            let tag = match node {
+                Node::Html(html) => HtmlTag::parse(&html.value)
+                    .filter(|tag| html::INLINE_TAGS.contains(&tag.name.as_str())),
+                _ => None,
+            };
+
+            let Some(tag) = tag else {
+                self.push_node(node);
+                continue;
+            };
+
+            if tag.name == "br" {
+                self.text.push('\n');
+            } else if tag.closing {
+                if let Some(i) = open_tags.iter().rposition(|(name, _, _)| *name == tag.name) {
+                    for (_, style, start) in open_tags.drain(i..).rev() {
+                        if let Some(style) = style {
+                            self.push_span(start, style);
+                        }
+                    }
+                }
+            } else if !tag.self_closing {
+                // Tags without a style, like a `<span>` without a color, are
+                // still tracked so they pair with their own closing tag.
+                let style = InlineStyle::from_html_tag(&tag);
+                open_tags.push((tag.name, style, self.text.len()));
+            }
+        }
+
+        for (_, style, start) in open_tags.into_iter().rev() {
+            if let Some(style) = style {
+                self.push_span(start, style);
+            }
+        }
+    }
+
+    fn push_node(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) => self.text.push_str(&text.value),
+            Node::Break(_) => self.text.push('\n'),
+            Node::Emphasis(emphasis) => self.push_styled(&emphasis.children, InlineStyle::Emphasis),
+            Node::Strong(strong) => self.push_styled(&strong.children, InlineStyle::Strong),
+            Node::Delete(delete) => self.push_styled(&delete.children, InlineStyle::Delete),
+            Node::Link(link) => self.push_styled(&link.children, InlineStyle::Link(link.url.clone())),
+            Node::InlineCode(code) => {
+                let start = self.text.len();
+                self.text.push_str(&code.value);
+                self.push_span(start, InlineStyle::Code);
+            },
+            Node::InlineMath(math) => {
+                let start = self.text.len();
+                self.text.push_str(&math.value);
+                self.push_span(start, InlineStyle::Code);
+            },
+            Node::Html(html) => self.text.push_str(&html.value),
+            _ => {}
+        }
+    }
+
+    fn push_styled(&mut self, children: &[Node], style: InlineStyle) {
+        let start = self.text.len();
+        self.push_nodes(children);
+        self.push_span(start, style);
+    }
+
+    fn push_span(&mut self, start: usize, style: InlineStyle) {
+        if start < self.text.len() {
+            self.spans.push(InlineSpan {
+                range: start..self.text.len(),
+                style,
+            });
+        }
+    }
+}
+
+/// A piece of the text of inline content and the span of source it was
+/// rendered from.
+#[derive(Debug, Clone)]
+struct SourceSegment {
+    text: Range<usize>,
+    source: Range<usize>,
+    /// Whether the text is a copy of its source, so offsets within it map
+    /// one to one.
+    exact: bool,
+}
+
+/// Maps byte offsets in the text of inline content back to the markdown
+/// source it was parsed from.
+///
+/// Offsets within plain text map exactly. Within text that differs from its
+/// source, like code spans or escaped characters, offsets are widened to the
+/// whole node, and offsets between nodes snap to the nearest node.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    segments: Vec<SourceSegment>,
+    len: usize,
+}
+
+impl SourceMap {
+    /// Builds the map of the same inline nodes `InlineContent::from_nodes`
+    /// builds the text of.
+    pub fn from_nodes(nodes: &[Node]) -> Self {
+        let mut map = Self::default();
+        map.push_nodes(nodes);
+        map
+    }
+
+    /// Whether none of the text has a position in the source.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Maps the offset where a range of text starts.
+    pub fn start_offset(&self, offset: usize) -> Option<usize> {
+        let segment = self.segments.iter().find(|segment| segment.text.end > offset)?;
+        Some(segment.source.start + self.delta(segment, offset).unwrap_or(0))
+    }
+
+    /// Maps the offset where a range of text ends.
+    pub fn end_offset(&self, offset: usize) -> Option<usize> {
+        let segment = self.segments.iter().rev().find(|segment| segment.text.start < offset)?;
+        Some(match self.delta(segment, offset) {
+            Some(delta) => segment.source.start + delta,
+            None => segment.source.end,
+        })
+    }
+
+    /// Gets how far into an exact segment an offset is.
+    fn delta(&self, segment: &SourceSegment, offset: usize) -> Option<usize> {
+        (segment.exact && segment.text.contains(&offset)).then(|| offset - segment.text.start)
+    }
+
+    fn push_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            let tag = match node {
+                Node::Html(html) => HtmlTag::parse(&html.value)
+                    .filter(|tag| html::INLINE_TAGS.contains(&tag.name.as_str())),
+                _ => None,
+            };
+
+            match tag {
+                Some(tag) if tag.name == "br" => self.push_text(node, 1),
+                Some(_) => {},
+                None => self.push_node(node),
+            }
+        }
+    }
+
+    fn push_node(&mut self, node: &Node) {
+        match node {
+            Node::Text(text) => self.push_text(node, text.value.len()),
+            Node::Break(_) => self.push_text(node, 1),
+            Node::Emphasis(emphasis) => self.push_nodes(&emphasis.children),
+            Node::Strong(strong) => self.push_nodes(&strong.children),
+            Node::Delete(delete) => self.push_nodes(&delete.children),
+            Node::Link(link) => self.push_nodes(&link.children),
+            Node::InlineCode(code) => self.push_text(node, code.value.len()),
+            Node::InlineMath(math) => self.push_text(node, math.value.len()),
+            Node::Html(html) => self.push_text(node, html.value.len()),
+            _ => {}
+        }
+    }
+
+    /// Adds the text of a node, which is only mapped if the node has a position.
+    fn push_text(&mut self, node: &Node, len: usize) {
+        let text = self.len..self.len + len;
+        self.len += len;
+
+        if let Some(position) = node.position() && len > 0 {
+            let source = position.start.offset..position.end.offset;
+            self.segments.push(SourceSegment {
+                exact: source.len() == len && !matches!(node, Node::Break(_)),
+                text,
+                source,
+            });
+        }
+    }
+}
+
+/// A find match to highlight, as a byte range and whether it is the current match.
+pub type Highlight = (Range<usize>, bool);
+
+/// A label displaying `InlineContent`, with find highlights layered on top
+/// of its formatting and clickable links.
+#[derive(Debug, Clone)]
+pub struct InlineLabel {
+    pub label: gtk4::Label,
+    content: Rc<RefCell<InlineContent>>,
+    scale: Rc<Cell<Option<f64>>>,
+    highlights: Rc<RefCell<Vec<Highlight>>>,
+}
+
+impl InlineLabel {
+    pub fn new(label: gtk4::Label) -> Self {
+        let inline_label = Self {
+            label,
+            content: Rc::new(RefCell::new(InlineContent::default())),
+            scale: Rc::new(Cell::new(None)),
+            highlights: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        inline_label.setup_links();
+        inline_label
+    }
+
+    /// Sets the content of the label, optionally scaling all of its text.
+    ///
+    /// Returns `false` without touching the label if nothing changed.
+    pub fn set_content(&self, content: InlineContent, scale: Option<f64>) -> bool {
+        if *self.content.borrow() == content && self.scale.get() == scale {
+            return false;
+        }
+
+        self.label.set_markup(&content.link_markup());
+        self.content.replace(content);
+        self.scale.set(scale);
+        self.apply_attributes();
+        true
+    }
+
+    /// Sets the byte ranges to highlight as find matches, flagging the current match.
+    pub fn set_highlights(&self, highlights: Vec<Highlight>) {
+        self.highlights.replace(highlights);
+        self.apply_attributes();
+    }
+
+    fn apply_attributes(&self) {
+        let attrs = self.content.borrow().attr_list(false);
+        if let Some(scale) = self.scale.get() {
+            attrs.insert(AttrFloat::new_scale(scale));
+        }
+
+        for (range, current) in self.highlights.borrow().iter() {
+            let (r, g, b) = if *current {
+                CURRENT_MATCH_BACKGROUND
+            } else {
+                MATCH_BACKGROUND
+            };
+
+            attrs.insert(with_range(AttrColor::new_background(r, g, b), range));
+            let (r, g, b) = MATCH_FOREGROUND;
+            attrs.insert(with_range(AttrColor::new_foreground(r, g, b), range));
+        }
+
+        self.label.set_attributes(Some(&attrs));
+    }
+
+    /// Gets the byte index of the text under a point in the label's coordinates.
+    fn index_at(label: &gtk4::Label, x: f64, y: f64) -> Option<usize> {
+        let (offset_x, offset_y) = label.layout_offsets();
+        let scale = f64::from(pango::SCALE);
+        let (inside, index, _) = label.layout().xy_to_index(
+            ((x - f64::from(offset_x)) * scale) as i32,
+            ((y - f64::from(offset_y)) * scale) as i32,
+        );
+
+        inside.then_some(index.max(0) as usize)
+    }
+
+    /// Opens activated links through the view the label is in, and shows the
+    /// expansions of abbreviations as tooltips.
+    fn setup_links(&self) {
+        self.label.connect_activate_link(|label, uri| {
+            view::activate_link(label, uri);
+            glib::Propagation::Stop
+        });
+
+        let motion = gtk4::EventControllerMotion::new();
+        motion.connect_motion(glib::clone!(
+            #[strong(rename_to = content)] self.content,
+            move |controller, x, y| {
+                let Some(label) = controller.widget().and_downcast::<gtk4::Label>() else {
+                    return;
+                };
+
+                let content = content.borrow();
+                let abbreviation = Self::index_at(&label, x, y)
+                    .and_then(|index| content.abbreviation_at(index));
+                label.set_tooltip_text(abbreviation);
+            }
+        ));
+
+        self.label.add_controller(motion);
+    }
+}
+
+/// Restricts an attribute to a byte range.
+pub fn with_range(attr: impl Into<pango::Attribute>, range: &Range<usize>) -> pango::Attribute {
+    let mut attr = attr.into();
+    attr.set_start_index(range.start as u32);
+    attr.set_end_index(range.end as u32);
+    attr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::ParserFlavor;
+
+    fn content(markdown: &str) -> InlineContent {
+        let parse_options = ParserFlavor::Gfm.parse_options().expect("GFM has parse options");
+        let mdast = markdown::to_mdast(markdown, &parse_options).expect("GFM always parses");
+        let Some(Node::Paragraph(paragraph)) = mdast.children().and_then(|children| children.first()) else {
+            panic!("expected a paragraph");
+        };
+        InlineContent::from_nodes(&paragraph.children)
+    }
+
+    fn spans(content: &InlineContent) -> Vec<(&str, &InlineStyle)> {
+        content.spans.iter()
+            .map(|span| (&content.text[span.range.clone()], &span.style))
+            .collect()
+    }
+
+    const RED: InlineStyle = InlineStyle::Foreground(0xffff, 0, 0);
+
+    #[test]
+    fn styleless_span_closes_itself() {
+        let content = content(r#"<span style="color: #ff0000">a <span>b</span> c</span> d"#);
+        assert_eq!(content.text, "a b c d");
+        assert_eq!(spans(&content), [("a b c", &RED)]);
+    }
+
+    #[test]
+    fn tags_pair_across_siblings() {
+        let content = content(r#"<b>a *b* <span style="color: #ff0000">c</span></b> <i>d"#);
+        assert_eq!(content.text, "a b c d");
+        assert_eq!(spans(&content), [
+            ("b", &InlineStyle::Emphasis),
+            ("c", &RED),
+            ("a b c", &InlineStyle::Strong),
+            ("d", &InlineStyle::Emphasis),
+        ]);
+    }
+
+    #[test]
+    fn closing_tag_closes_inner_tags() {
+        let content = content("<b>a <i>b</b> c</i>");
+        assert_eq!(content.text, "a b c");
+        assert_eq!(spans(&content), [
+            ("b", &InlineStyle::Emphasis),
+            ("a b", &InlineStyle::Strong),
+        ]);
+    }
+}
@@ -1,28 +1,155 @@
-use markdown::mdast::{Node, Paragraph, Text};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::ops::Range;
+use markdown::mdast::{Code, Image, Link, Node, Paragraph, Strong, Text};
+use markdown::unist::Position;
 
+use super::frontmatter::{FrontMatter, FrontMatterDisplay, FrontMatterFormat};
+use super::html::{self, HtmlPolicy, InlineHtmlPolicy};
+use super::inline::{InlineContent, SourceMap};
 use super::util;
 
 /// Types of lists while rendering.
 #[derive(Debug, Clone)]
-pub enum RenderListType {
+enum RenderListType {
     Bullet,
     Ordered,
 }
 
 /// Marker for list items while rendering.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RenderMarker {
+    /// An item of an unordered list.
     Bullet,
+    /// An item of an ordered list, with its number.
     Ordered(u32),
+    /// A task list item, and whether it is checked.
+    Task(bool),
+}
+
+impl RenderMarker {
+    /// Gets the text the marker is displayed as.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Bullet => "•".to_owned(),
+            Self::Ordered(index) => format!("{}.", index),
+            Self::Task(true) => "☑".to_owned(),
+            Self::Task(false) => "☐".to_owned(),
+        }
+    }
+}
+
+/// The kind of a GitHub alert, a blockquote starting with e.g. `[!NOTE]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlertKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl AlertKind {
+    const ALL: [Self; 5] = [Self::Note, Self::Tip, Self::Important, Self::Warning, Self::Caution];
+
+    /// Gets the lowercase name of the kind, as used in CSS classes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Note => "note",
+            Self::Tip => "tip",
+            Self::Important => "important",
+            Self::Warning => "warning",
+            Self::Caution => "caution",
+        }
+    }
+
+    /// Gets the title shown at the top of the alert.
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Tip => "Tip",
+            Self::Important => "Important",
+            Self::Warning => "Warning",
+            Self::Caution => "Caution",
+        }
+    }
+
+    /// Removes the alert marker from the start of a blockquote's first
+    /// paragraph, returning the alert's kind.
+    fn strip_marker(paragraph: &mut Paragraph) -> Option<Self> {
+        let Some(Node::Text(text)) = paragraph.children.first_mut() else {
+            return None;
+        };
+
+        let rest = text.value.strip_prefix("[!")?;
+        let (name, rest) = rest.split_once(']')?;
+        let kind = Self::ALL.into_iter().find(|kind| kind.name().eq_ignore_ascii_case(name))?;
+
+        text.value = rest.trim_start().to_owned();
+        if text.value.is_empty() {
+            paragraph.children.remove(0);
+        }
+        Some(kind)
+    }
 }
 
 /// A block of content to be rendered.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RenderBlock {
+    /// The block-level node rendered by the block, e.g. a paragraph.
     pub node: Node,
+    /// How deeply the block is nested in lists, with top-level lists at 0.
     pub depth: usize,
+    /// The list marker shown before the block, for the first block of a list item.
     pub marker: Option<RenderMarker>,
+    /// Whether the block is a later block of a list item, which lines up
+    /// with the content after the item's marker.
     pub is_continuation: bool,
+    /// The span of markdown source this block was rendered from.
+    pub position: Option<Position>,
+    /// The alert the block is part of, if any.
+    pub alert: Option<AlertKind>,
+}
+
+impl RenderBlock {
+    /// Returns the byte range of markdown source this block was rendered from.
+    pub fn source_range(&self) -> Option<Range<usize>> {
+        self.position.as_ref().map(|position| position.start.offset..position.end.offset)
+    }
+
+    /// Returns the 1-based range of markdown source lines this block was
+    /// rendered from, with the end exclusive.
+    pub fn source_lines(&self) -> Option<Range<usize>> {
+        self.position.as_ref().map(|position| position.start.line..position.end.line + 1)
+    }
+
+    /// Maps a byte range of the block's text to the markdown source it was
+    /// rendered from.
+    ///
+    /// Only the text of paragraphs and headings maps back to the source, the
+    /// text of other blocks is laid out differently, e.g. tables separate
+    /// their cells with tabs, so the whole block is used.
+    pub fn source_range_of(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let block = self.source_range()?;
+        let children = match &self.node {
+            Node::Paragraph(paragraph) => &paragraph.children,
+            Node::Heading(heading) => &heading.children,
+            _ => return Some(block),
+        };
+
+        // Text added while rendering, like the title of an alert, has no source.
+        let map = SourceMap::from_nodes(children);
+        if map.is_empty() {
+            return Some(block);
+        }
+
+        let start = map.start_offset(range.start).unwrap_or(block.end).clamp(block.start, block.end);
+        let end = map.end_offset(range.end).unwrap_or(block.start).clamp(start, block.end);
+        Some(start..end)
+    }
 }
 
 /// Represents the scope of a list while rendering.
@@ -36,7 +163,8 @@ struct RenderListScope {
 #[derive(Default, Debug, Clone)]
 struct RenderWalkContext {
     list_stack: Vec<RenderListScope>,
-    paragraph_stack: Vec<Vec<Node>>,
+    paragraph_stack: Vec<Paragraph>,
+    alert: Option<AlertKind>,
 }
 
 impl RenderWalkContext {
@@ -59,18 +187,53 @@ impl RenderWalkContext {
     }
 }
 
+/// Options that affect how the AST is flattened into blocks.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenderOptions {
+    /// Merges adjacent paragraphs into a single block, which keeps the widget
+    /// count down for long documents at the cost of per-paragraph styling.
+    pub merge_paragraphs: bool,
+    /// How inline HTML outside of the supported subset is displayed.
+    pub inline_html: InlineHtmlPolicy,
+    /// How block-level HTML is displayed.
+    pub html: HtmlPolicy,
+    /// How front matter is displayed.
+    pub front_matter: FrontMatterDisplay,
+}
+
 /// An intermediate representation of the parsed markdown for rendering.
+///
+/// The AST is flattened into a list of blocks, each of which the view
+/// renders as one widget. Nesting, such as lists and blockquotes, is kept
+/// on the blocks as their depth, markers and alert.
 #[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RenderBuffer {
+    /// The blocks, in document order.
     pub blocks: Vec<RenderBlock>,
+    /// The options used when the buffer is set.
+    pub options: RenderOptions,
 }
 
 impl RenderBuffer {
     /// Sets the render buffer by walking the AST starting from the given node.
     pub fn set(&mut self, node: &Node) {
         self.blocks.clear();
+
+        // Sections are only paired when HTML is rendered, otherwise their tags
+        // are shown as source or ignored like any other HTML.
+        let mut node = node.clone();
+        if self.options.html == HtmlPolicy::Render {
+            html::pair_details(&mut node);
+        }
+
+        let mut definitions = HashMap::new();
+        collect_definitions(&node, &mut definitions);
+        resolve_references(&mut node, &definitions);
+
         let mut walk_ctx = RenderWalkContext::default();
-        self.walk(node, &mut walk_ctx);
+        self.walk(&node, &mut walk_ctx);
         self.drain_paragraph_stack(&mut walk_ctx);
     }
     
@@ -78,9 +241,10 @@ impl RenderBuffer {
     fn drain_paragraph_stack(&mut self, ctx: &mut RenderWalkContext) {
         if !ctx.paragraph_stack.is_empty() {
             let len = ctx.paragraph_stack.len();
+            let position = Self::merged_position(&ctx.paragraph_stack);
             let mut children = Vec::new();
             for (i, paragraph) in ctx.paragraph_stack.drain(..).enumerate() {
-                children.extend(paragraph);
+                children.extend(paragraph.children);
                 
                 if i < len - 1 {
                     children.push(Node::Text(Text {
@@ -92,7 +256,7 @@ impl RenderBuffer {
             
             let node = Node::Paragraph(Paragraph {
                 children,
-                position: None,
+                position: position.clone(),
             });
             
             self.push_block(RenderBlock {
@@ -100,15 +264,36 @@ impl RenderBuffer {
                 depth: ctx.depth(),
                 marker: None,
                 is_continuation: false,
+                position,
+                alert: ctx.alert,
             });
         }
     }
 
+    /// Returns the span covering all of the given paragraphs.
+    fn merged_position(paragraphs: &[Paragraph]) -> Option<Position> {
+        let start = paragraphs.first()?.position.as_ref()?;
+        let end = paragraphs.last()?.position.as_ref()?;
+
+        Some(Position {
+            start: start.start.clone(),
+            end: end.end.clone(),
+        })
+    }
+
+    /// Returns true if the node is an element made from HTML, which the HTML
+    /// policy doesn't render.
+    fn skips_html_element(&self, node: &Node) -> bool {
+        html::is_html_element(node) && self.options.html != HtmlPolicy::Render
+    }
+
     /// Recursively walks the AST and populates the render buffer.
     fn walk(&mut self, node: &Node, ctx: &mut RenderWalkContext) {
-        // Merge adjacent paragraphs
-        if let Node::Paragraph(paragraph) = node {
-            ctx.paragraph_stack.push(paragraph.children.clone());
+        // Merge adjacent paragraphs if requested
+        if self.options.merge_paragraphs
+            && let Node::Paragraph(paragraph) = node
+        {
+            ctx.paragraph_stack.push(paragraph.clone());
             return;
         }
         
@@ -142,9 +327,14 @@ impl RenderBuffer {
                     RenderListType::Ordered => RenderMarker::Ordered(ctx.next_marker())
                 };
 
+                let marker = match node {
+                    Node::ListItem(item) if let Some(checked) = item.checked => RenderMarker::Task(checked),
+                    _ => marker,
+                };
+
                 if let Some(children) = node.children() {
                     for child in children {
-                        if util::is_block_node(child) {
+                        if util::is_block_node(child) && !self.skips_html_element(child) {
                             let is_first = first_block;
                             
                             let marker = is_first.then(|| {
@@ -157,6 +347,8 @@ impl RenderBuffer {
                                 depth: ctx.depth(),
                                 marker,
                                 is_continuation: !is_first,
+                                position: child.position().cloned(),
+                                alert: ctx.alert,
                             });
                         } else {
                             self.walk(child, ctx);
@@ -165,12 +357,126 @@ impl RenderBuffer {
                 }
             },
 
+            Node::Blockquote(blockquote) if ctx.alert.is_none() => {
+                let mut children = blockquote.children.clone();
+                let alert = match children.first_mut() {
+                    Some(Node::Paragraph(paragraph)) => AlertKind::strip_marker(paragraph),
+                    _ => None,
+                };
+
+                if let Some(Node::Paragraph(paragraph)) = children.first()
+                    && paragraph.children.is_empty()
+                {
+                    children.remove(0);
+                }
+
+                if let Some(alert) = alert {
+                    ctx.alert = Some(alert);
+                    self.push_block(RenderBlock {
+                        node: Node::Paragraph(Paragraph {
+                            children: vec![Node::Strong(Strong {
+                                children: vec![Node::Text(Text {
+                                    value: alert.title().to_owned(),
+                                    position: None,
+                                })],
+                                position: None,
+                            })],
+                            position: None,
+                        }),
+                        depth: ctx.depth(),
+                        marker: None,
+                        is_continuation: false,
+                        position: blockquote.position.clone(),
+                        alert: Some(alert),
+                    });
+                } else {
+                    children = blockquote.children.clone();
+                }
+
+                for child in &children {
+                    self.walk(child, ctx);
+                }
+
+                self.drain_paragraph_stack(ctx);
+                ctx.alert = None;
+            },
+
+            // Elements made from HTML are only rendered along with the rest of it.
+            _ if self.skips_html_element(node) => {},
+
+            Node::Html(html) => match self.options.html {
+                HtmlPolicy::Ignore => {},
+                HtmlPolicy::Source => self.push_block(RenderBlock {
+                    node: Node::Code(Code {
+                        value: html.value.clone(),
+                        position: html.position.clone(),
+                        lang: Some("html".to_owned()),
+                        meta: None,
+                    }),
+                    depth: ctx.depth(),
+                    marker: None,
+                    is_continuation: false,
+                    position: html.position.clone(),
+                    alert: ctx.alert,
+                }),
+                HtmlPolicy::Render => {
+                    for child in html::html_block_to_nodes(&html.value, html.position.as_ref()) {
+                        self.walk(&child, ctx);
+                    }
+                },
+            },
+
+            // Math is shown as its TeX source until there is a math renderer.
+            Node::Math(math) => self.push_block(RenderBlock {
+                node: Node::Code(Code {
+                    value: math.value.clone(),
+                    position: math.position.clone(),
+                    lang: Some("latex".to_owned()),
+                    meta: None,
+                }),
+                depth: ctx.depth(),
+                marker: None,
+                is_continuation: false,
+                position: math.position.clone(),
+                alert: ctx.alert,
+            }),
+
+            Node::Yaml(_) | Node::Toml(_) => if self.options.front_matter == FrontMatterDisplay::Table
+                && let Some(front_matter) = FrontMatter::from_node(node)
+            {
+                let position = node.position().cloned();
+                let node = if front_matter.entries.is_empty() {
+                    Node::Code(Code {
+                        value: front_matter.raw.clone(),
+                        position: position.clone(),
+                        lang: Some(match front_matter.format {
+                            FrontMatterFormat::Yaml => "yaml".to_owned(),
+                            FrontMatterFormat::Toml => "toml".to_owned(),
+                        }),
+                        meta: None,
+                    })
+                } else {
+                    front_matter.to_table(position.clone())
+                };
+
+                self.push_block(RenderBlock {
+                    node,
+                    depth: 0,
+                    marker: None,
+                    is_continuation: false,
+                    position,
+                    alert: None,
+                });
+            },
+
             _ => if util::is_block_node(node) {
                 self.push_block(RenderBlock {
                     node: node.clone(),
                     depth: ctx.depth(),
                     marker: None,
                     is_continuation: false,
+                    position: node.position().cloned(),
+                    alert: ctx.alert,
                 });
             } else if let Some(children) = node.children() {
                 #[cfg(debug_assertions)]
@@ -188,8 +494,301 @@ impl RenderBuffer {
         }
     }
 
+    /// Returns the index of the block rendered from the given byte offset.
+    ///
+    /// Offsets between blocks (e.g. blank lines) resolve to the closest
+    /// preceding block.
+    pub fn block_index_at_source_offset(&self, offset: usize) -> Option<usize> {
+        self.block_index_at(offset, RenderBlock::source_range)
+    }
+
+    /// Returns the index of the block rendered from the given 1-based line
+    /// of the markdown source.
+    ///
+    /// Lines between blocks resolve to the closest preceding block.
+    pub fn block_index_at_source_line(&self, line: usize) -> Option<usize> {
+        self.block_index_at(line, RenderBlock::source_lines)
+    }
+
+    fn block_index_at(&self, at: usize, range_of: impl Fn(&RenderBlock) -> Option<Range<usize>>) -> Option<usize> {
+        let mut closest = None;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let Some(range) = range_of(block) else {
+                continue;
+            };
+
+            if range.contains(&at) {
+                return Some(i);
+            } else if range.start <= at {
+                closest = Some(i);
+            }
+        }
+        closest
+    }
+
+    /// Dumps the blocks as text, one line per block, for diffing in tests.
+    ///
+    /// Blocks are indented by their depth, followed by their kind, details
+    /// such as their marker or alert, and their text without formatting.
+    /// Table rows and the blocks inside `<details>` follow on their own lines.
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+        self.dump_blocks(&mut output, 0);
+        output
+    }
+
+    fn dump_blocks(&self, output: &mut String, indent: usize) {
+        for block in &self.blocks {
+            let depth = indent + block.depth;
+            let _ = write!(output, "{}{}", "  ".repeat(depth), util::node_variant_name(&block.node));
+
+            match &block.node {
+                Node::Heading(heading) => {
+                    let _ = write!(output, " depth={}", heading.depth);
+                },
+                Node::Code(code) => if let Some(lang) = &code.lang {
+                    let _ = write!(output, " lang={}", lang);
+                },
+                Node::MdxJsxFlowElement(element) => {
+                    let _ = write!(output, " <{}>", element.name.as_deref().unwrap_or_default());
+                },
+                _ => {},
+            }
+
+            if let Some(marker) = &block.marker {
+                let _ = write!(output, " marker={}", marker.label());
+            }
+            if block.is_continuation {
+                output.push_str(" continuation");
+            }
+            if let Some(alert) = block.alert {
+                let _ = write!(output, " alert={}", alert.name());
+            }
+
+            let text = match &block.node {
+                Node::Paragraph(paragraph) => Some(InlineContent::from_nodes(&paragraph.children).text),
+                Node::Heading(heading) => Some(InlineContent::from_nodes(&heading.children).text),
+                Node::Code(code) => Some(code.value.clone()),
+                Node::MdxJsxFlowElement(element) if html::is_element(&block.node, "img") => {
+                    html::element_attribute(element, "src").map(str::to_owned)
+                },
+                Node::MdxJsxFlowElement(element) if html::is_element(&block.node, "details") => {
+                    html::element_attribute(element, "summary").map(str::to_owned)
+                },
+                _ => None,
+            };
+            if let Some(text) = text {
+                let _ = write!(output, " {:?}", text);
+            }
+            output.push('\n');
+
+            match &block.node {
+                Node::Table(table) => for row in &table.children {
+                    let cells = row.children().into_iter().flatten()
+                        .map(|cell| InlineContent::from_nodes(cell.children().map_or(&[][..], Vec::as_slice)).text)
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(output, "{}| {} |", "  ".repeat(depth + 1), cells.join(" | "));
+                },
+                Node::MdxJsxFlowElement(element) if html::is_element(&block.node, "details") => {
+                    let mut nested = RenderBuffer {
+                        options: self.options.clone(),
+                        ..Default::default()
+                    };
+                    nested.set(&Node::Root(markdown::mdast::Root {
+                        children: element.children.clone(),
+                        position: None,
+                    }));
+                    nested.dump_blocks(output, depth + 1);
+                },
+                _ => {},
+            }
+        }
+    }
+
     /// Pushes a block to the render buffer.
-    fn push_block(&mut self, block: RenderBlock) {
+    fn push_block(&mut self, mut block: RenderBlock) {
+        html::filter_inline_html(&mut block.node, self.options.inline_html);
         self.blocks.push(block);
     }
-}
\ No newline at end of file
+}
+
+/// Collects the url and title of every definition by its identifier.
+fn collect_definitions(node: &Node, definitions: &mut HashMap<String, (String, Option<String>)>) {
+    if let Node::Definition(definition) = node {
+        definitions.entry(definition.identifier.clone())
+            .or_insert_with(|| (definition.url.clone(), definition.title.clone()));
+    }
+
+    for child in node.children().into_iter().flatten() {
+        collect_definitions(child, definitions);
+    }
+}
+
+/// Replaces link and image references with the links and images they refer
+/// to. References without a definition are replaced by their text.
+fn resolve_references(node: &mut Node, definitions: &HashMap<String, (String, Option<String>)>) {
+    let Some(children) = node.children_mut() else {
+        return;
+    };
+
+    let mut resolved = Vec::with_capacity(children.len());
+    for mut child in std::mem::take(children) {
+        resolve_references(&mut child, definitions);
+
+        match child {
+            Node::LinkReference(reference) => match definitions.get(&reference.identifier) {
+                Some((url, title)) => resolved.push(Node::Link(Link {
+                    children: reference.children,
+                    position: reference.position,
+                    url: url.clone(),
+                    title: title.clone(),
+                })),
+                None => resolved.extend(reference.children),
+            },
+            Node::ImageReference(reference) => match definitions.get(&reference.identifier) {
+                Some((url, title)) => resolved.push(Node::Image(Image {
+                    position: reference.position,
+                    alt: reference.alt,
+                    url: url.clone(),
+                    title: title.clone(),
+                })),
+                None => resolved.push(Node::Text(Text {
+                    value: reference.alt,
+                    position: reference.position,
+                })),
+            },
+            child => resolved.push(child),
+        }
+    }
+
+    *children = resolved;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::ParserFlavor;
+
+    fn dump(markdown: &str, options: RenderOptions) -> String {
+        let parse_options = ParserFlavor::Gfm.parse_options().expect("GFM has parse options");
+        let mdast = markdown::to_mdast(markdown, &parse_options).expect("GFM always parses");
+
+        let mut buffer = RenderBuffer {
+            options,
+            ..Default::default()
+        };
+        buffer.set(&mdast);
+        buffer.dump()
+    }
+
+    #[test]
+    fn separate_paragraphs() {
+        assert_eq!(
+            dump("One.\n\nTwo.\n\n# Three\n\nFour.", RenderOptions::default()),
+            "Paragraph \"One.\"\n\
+             Paragraph \"Two.\"\n\
+             Heading depth=1 \"Three\"\n\
+             Paragraph \"Four.\"\n"
+        );
+    }
+
+    #[test]
+    fn merged_paragraphs() {
+        let options = RenderOptions {
+            merge_paragraphs: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            dump("One.\n\nTwo.\n\n# Three\n\nFour.", options),
+            "Paragraph \"One.\\n\\nTwo.\"\n\
+             Heading depth=1 \"Three\"\n\
+             Paragraph \"Four.\"\n"
+        );
+    }
+
+    #[test]
+    fn nested_task_lists() {
+        let markdown = "- [x] Done\n  - [ ] Nested\n\n    More of nested.\n- Plain\n\n1. First\n2. Second\n";
+        assert_eq!(
+            dump(markdown, RenderOptions::default()),
+            "Paragraph marker=☑ \"Done\"\n\
+             \x20 Paragraph marker=☐ \"Nested\"\n\
+             \x20 Paragraph continuation \"More of nested.\"\n\
+             Paragraph marker=• \"Plain\"\n\
+             Paragraph marker=1. \"First\"\n\
+             Paragraph marker=2. \"Second\"\n"
+        );
+    }
+
+    #[test]
+    fn alerts() {
+        let markdown = "> [!WARNING]\n> Careful.\n>\n> Really.\n\n> Just a quote.";
+        assert_eq!(
+            dump(markdown, RenderOptions::default()),
+            "Paragraph alert=warning \"Warning\"\n\
+             Paragraph alert=warning \"Careful.\"\n\
+             Paragraph alert=warning \"Really.\"\n\
+             Paragraph \"Just a quote.\"\n"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let markdown = "| A | *B* |\n| - | - |\n| 1 | `2` |\n| 3 | |";
+        assert_eq!(
+            dump(markdown, RenderOptions::default()),
+            "Table\n\
+             \x20 | A | B |\n\
+             \x20 | 1 | 2 |\n\
+             \x20 | 3 |  |\n"
+        );
+    }
+
+    #[test]
+    fn details() {
+        let markdown = "<details open>\n<summary>More</summary>\n\nHidden *text*.\n\n```rust\nfn main() {}\n```\n\n</details>\n\nAfter.";
+        assert_eq!(
+            dump(markdown, RenderOptions::default()),
+            "Paragraph \"Hidden text.\"\n\
+             Code lang=rust \"fn main() {}\"\n\
+             Paragraph \"After.\"\n"
+        );
+
+        let options = RenderOptions {
+            html: HtmlPolicy::Render,
+            ..Default::default()
+        };
+        assert_eq!(
+            dump(markdown, options),
+            "MdxJsxFlowElement <details> \"More\"\n\
+             \x20 Paragraph \"Hidden text.\"\n\
+             \x20 Code lang=rust \"fn main() {}\"\n\
+             Paragraph \"After.\"\n"
+        );
+    }
+
+    #[test]
+    fn details_keep_block_html() {
+        let markdown = "<details><summary>x</summary>\n\n<img src=\"a.png\">\n\n</details>";
+        let mdast = markdown::to_mdast(markdown, &markdown::ParseOptions::gfm()).expect("GFM always parses");
+
+        let mut buffer = RenderBuffer {
+            options: RenderOptions {
+                html: HtmlPolicy::Render,
+                inline_html: InlineHtmlPolicy::Strip,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        buffer.set(&mdast);
+
+        let [block] = buffer.blocks.as_slice() else {
+            panic!("expected a single details block, got {:?}", buffer.blocks);
+        };
+        assert!(html::is_element(&block.node, "details"));
+        assert!(matches!(
+            block.node.children().map(Vec::as_slice),
+            Some([Node::Html(html)]) if html.value == "<img src=\"a.png\">"
+        ));
+    }
+}
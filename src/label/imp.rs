@@ -0,0 +1,80 @@
+use std::cell::{Cell, RefCell};
+use gtk4::glib::{self, Properties};
+use gtk4::subclass::prelude::*;
+use gtk4::prelude::*;
+
+use crate::html::InlineHtmlPolicy;
+use crate::inline::InlineLabel;
+use crate::markup;
+use crate::view::ParserFlavor;
+
+#[derive(Properties)]
+#[properties(wrapper_type = super::MarkdownLabel)]
+pub struct MarkdownLabel {
+    pub(super) inline: InlineLabel,
+
+    /// The markdown shown by the label.
+    #[property(get, set)]
+    markdown: RefCell<String>,
+
+    /// Which markdown syntax the content is parsed as.
+    #[property(get, set, builder(ParserFlavor::default()))]
+    flavor: Cell<ParserFlavor>,
+
+    /// How inline HTML outside of the supported subset is displayed.
+    #[property(get, set, builder(InlineHtmlPolicy::default()))]
+    inline_html_policy: Cell<InlineHtmlPolicy>,
+}
+
+impl Default for MarkdownLabel {
+    fn default() -> Self {
+        let label = gtk4::Label::builder()
+            .css_classes(["cmark-label"])
+            .wrap(true)
+            .wrap_mode(gtk4::pango::WrapMode::WordChar)
+            .xalign(0.0)
+            .build();
+
+        Self {
+            inline: InlineLabel::new(label),
+            markdown: RefCell::new(String::new()),
+            flavor: Cell::new(ParserFlavor::default()),
+            inline_html_policy: Cell::new(InlineHtmlPolicy::default()),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for MarkdownLabel {
+    const NAME: &'static str = "MarkdownLabel";
+    type Type = super::MarkdownLabel;
+    type ParentType = gtk4::Box;
+}
+
+#[glib::derived_properties]
+impl ObjectImpl for MarkdownLabel {
+    fn constructed(&self) {
+        self.parent_constructed();
+        self.obj().add_css_class("cmark-markdown-label");
+        self.obj().append(&self.inline.label);
+
+        self.obj().connect_markdown_notify(|label| label.imp().render());
+        self.obj().connect_flavor_notify(|label| label.imp().render());
+        self.obj().connect_inline_html_policy_notify(|label| label.imp().render());
+    }
+}
+
+impl WidgetImpl for MarkdownLabel {}
+
+impl BoxImpl for MarkdownLabel {}
+
+impl MarkdownLabel {
+    fn render(&self) {
+        let content = markup::parse_inline(
+            &self.markdown.borrow(),
+            self.flavor.get(),
+            self.inline_html_policy.get(),
+        );
+        self.inline.set_content(content, None);
+    }
+}
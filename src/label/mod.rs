@@ -0,0 +1,34 @@
+mod imp;
+
+use gtk4::glib::subclass::types::ObjectSubclassIsExt as _;
+use gtk4::glib::{self, Object};
+
+glib::wrapper! {
+    /// A label showing inline markdown, for short content like a single
+    /// paragraph that doesn't need a full `MarkdownView`.
+    ///
+    /// Block content is flattened the same way as by `to_pango_markup`.
+    pub struct MarkdownLabel(ObjectSubclass<imp::MarkdownLabel>)
+        @extends gtk4::Widget, gtk4::Box,
+        @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget, gtk4::Orientable;
+}
+
+impl Default for MarkdownLabel {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
+
+impl MarkdownLabel {
+    /// Creates a new `MarkdownLabel` showing the given markdown.
+    pub fn new(markdown: &str) -> Self {
+        Object::builder()
+            .property("markdown", markdown)
+            .build()
+    }
+
+    /// Gets the underlying `Label`, e.g. to set its wrapping or ellipsizing.
+    pub fn label(&self) -> gtk4::Label {
+        self.imp().inline.label.clone()
+    }
+}
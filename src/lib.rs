@@ -1,11 +1,35 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod backend;
 pub mod blocks;
+#[cfg(feature = "sourceview")]
+mod editor;
+pub mod export;
+mod find;
+mod frontmatter;
+mod highlight;
+mod html;
+mod images;
+mod inline;
+mod label;
+mod markup;
 mod view;
-mod ir;
+pub mod ir;
+mod selection;
+mod textview;
 mod util;
 
-pub use view::MarkdownView;
+#[cfg(feature = "sourceview")]
+pub use editor::MarkdownEditor;
+pub use export::{markdown_to_ansi, markdown_to_pdf, markdown_to_text};
+pub use find::FindOptions;
+pub use frontmatter::{FrontMatter, FrontMatterDisplay, FrontMatterFormat};
+pub use highlight::{CodeTheme, HighlightBackend};
+pub use html::{HtmlPolicy, InlineHtmlPolicy};
+pub use images::ImageResolver;
+pub use label::MarkdownLabel;
+pub use markup::{to_pango_markup, MarkupOptions};
+pub use view::{MarkdownView, ParserFlavor, RenderMode};
 
 // Re-export dependencies for convenience
 pub use futures_signals;
\ No newline at end of file
@@ -0,0 +1,178 @@
+use gtk4::glib;
+use markdown::mdast::{Node, Root};
+
+use crate::html::{self, InlineHtmlPolicy};
+use crate::inline::{self, InlineContent, InlineSpan, InlineStyle};
+use crate::ir::{RenderBuffer, RenderOptions};
+use crate::view::ParserFlavor;
+
+const THEMATIC_BREAK: &str = "———";
+
+/// Options for `to_pango_markup`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkupOptions {
+    /// Which markdown syntax the content is parsed as. `Custom` parses it as
+    /// CommonMark.
+    pub flavor: ParserFlavor,
+    /// How inline HTML outside of the supported subset is displayed.
+    pub inline_html: InlineHtmlPolicy,
+    /// Keeps links as `<a>` tags. `GtkLabel` supports these, but plain Pango
+    /// markup, e.g. in notification bodies, doesn't, so links are otherwise
+    /// only styled like links.
+    pub links: bool,
+}
+
+/// Converts markdown to Pango markup for labels, tooltips and notifications.
+///
+/// Inline formatting maps to Pango tags. Block content is flattened to
+/// text: paragraphs are separated by blank lines, headings are bold, list
+/// items keep their markers, code blocks are monospace and table cells are
+/// separated by tabs.
+pub fn to_pango_markup(markdown: &str, options: &MarkupOptions) -> String {
+    let content = parse_inline(markdown, options.flavor, options.inline_html);
+    content.to_markup(
+        |style| pango_tags(style, options.links),
+        |text| glib::markup_escape_text(text).into(),
+    )
+}
+
+/// Parses markdown and flattens it to a single run of inline content.
+pub(crate) fn parse_inline(markdown: &str, flavor: ParserFlavor, inline_html: InlineHtmlPolicy) -> InlineContent {
+    let parse_options = flavor.parse_options().unwrap_or_default();
+    let Ok(mdast) = markdown::to_mdast(markdown, &parse_options) else {
+        return InlineContent {
+            text: markdown.to_owned(),
+            ..Default::default()
+        };
+    };
+
+    let mut buffer = RenderBuffer {
+        options: RenderOptions {
+            inline_html,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    buffer.set(&mdast);
+    flatten(&buffer)
+}
+
+/// Flattens every block of a render buffer into inline content.
+fn flatten(buffer: &RenderBuffer) -> InlineContent {
+    let mut content = InlineContent::default();
+    let mut previous_in_list = false;
+
+    for block in &buffer.blocks {
+        let in_list = block.marker.is_some() || block.is_continuation || block.depth > 0;
+        if !content.text.is_empty() {
+            // List items follow each other without a blank line.
+            let separator = if block.marker.is_some() && previous_in_list { "\n" } else { "\n\n" };
+            content.push_text(separator, None);
+        }
+        previous_in_list = in_list;
+
+        content.push_text(&"  ".repeat(block.depth), None);
+        if let Some(marker) = &block.marker {
+            content.push_text(&format!("{} ", marker.label()), None);
+        }
+
+        push_block(&mut content, buffer, &block.node);
+    }
+
+    content
+}
+
+fn push_block(content: &mut InlineContent, buffer: &RenderBuffer, node: &Node) {
+    match node {
+        Node::Paragraph(paragraph) => content.append(&InlineContent::from_nodes(&paragraph.children)),
+        Node::Heading(heading) => content.append(&strong(InlineContent::from_nodes(&heading.children))),
+        Node::Code(code) => content.push_text(code.value.trim_end_matches('\n'), Some(InlineStyle::Code)),
+        Node::Table(table) => {
+            for (r, row) in table.children.iter().enumerate() {
+                if r > 0 {
+                    content.push_text("\n", None);
+                }
+
+                for (c, cell) in row.children().into_iter().flatten().enumerate() {
+                    if c > 0 {
+                        content.push_text("\t", None);
+                    }
+
+                    let cell = InlineContent::from_nodes(cell.children().map_or(&[][..], Vec::as_slice));
+                    content.append(&if r == 0 { strong(cell) } else { cell });
+                }
+            }
+        },
+        Node::ThematicBreak(_) => content.push_text(THEMATIC_BREAK, None),
+        Node::MdxJsxFlowElement(element) if html::is_element(node, "img") => {
+            content.push_text(html::element_attribute(element, "alt").unwrap_or_default(), None);
+        },
+        Node::MdxJsxFlowElement(element) if html::is_element(node, "details") => {
+            let summary = html::element_attribute(element, "summary").unwrap_or_default();
+            content.push_text(summary, Some(InlineStyle::Strong));
+
+            let mut nested = RenderBuffer {
+                options: buffer.options.clone(),
+                ..Default::default()
+            };
+            nested.set(&Node::Root(Root {
+                children: element.children.clone(),
+                position: None,
+            }));
+
+            let nested = flatten(&nested);
+            if !nested.text.is_empty() {
+                content.push_text("\n\n", None);
+                content.append(&nested);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Makes all of the content bold.
+fn strong(mut content: InlineContent) -> InlineContent {
+    content.spans.insert(0, InlineSpan {
+        range: 0..content.text.len(),
+        style: InlineStyle::Strong,
+    });
+    content
+}
+
+fn color((r, g, b): (u16, u16, u16)) -> String {
+    format!("#{:04x}{:04x}{:04x}", r, g, b)
+}
+
+/// Gets the opening and closing Pango tags for an inline style.
+fn pango_tags(style: &InlineStyle, links: bool) -> (String, String) {
+    let tag = |name: &str| (format!("<{}>", name), format!("</{}>", name));
+    let span = |attributes: String| (format!("<span {}>", attributes), "</span>".to_owned());
+
+    match style {
+        InlineStyle::Emphasis => tag("i"),
+        InlineStyle::Strong => tag("b"),
+        InlineStyle::Delete => tag("s"),
+        InlineStyle::Code | InlineStyle::Keyboard => tag("tt"),
+        InlineStyle::Link(url) if links => (
+            format!("<a href=\"{}\">", glib::markup_escape_text(url)),
+            "</a>".to_owned(),
+        ),
+        InlineStyle::Link(_) => span(format!(
+            "foreground=\"{}\" underline=\"single\"",
+            color(inline::LINK_FOREGROUND),
+        )),
+        InlineStyle::Underline => tag("u"),
+        InlineStyle::Subscript => tag("sub"),
+        InlineStyle::Superscript => tag("sup"),
+        InlineStyle::Mark => span(format!(
+            "background=\"{}\" foreground=\"#000000\"",
+            color(inline::MARK_BACKGROUND),
+        )),
+        InlineStyle::Small => tag("small"),
+        InlineStyle::Abbreviation(_) => span(format!(
+            "underline=\"single\" underline_color=\"{}\"",
+            color(inline::ABBREVIATION_UNDERLINE),
+        )),
+        InlineStyle::Foreground(r, g, b) => span(format!("foreground=\"{}\"", color((*r, *g, *b)))),
+    }
+}
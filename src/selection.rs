@@ -0,0 +1,113 @@
+use std::ops::Range;
+use gtk4::graphene;
+use gtk4::pango;
+use gtk4::prelude::*;
+
+/// Separates cells within a row when a grid of labels is flattened to text.
+const CELL_SEPARATOR: &str = "\t";
+/// Separates rows when a grid of labels is flattened to text.
+const ROW_SEPARATOR: &str = "\n";
+
+/// Flattens a grid of labels into text, returning each label's byte span in it.
+fn grid_spans(rows: &[Vec<gtk4::Label>]) -> (String, Vec<(&gtk4::Label, Range<usize>)>) {
+    let mut text = String::new();
+    let mut spans = Vec::new();
+
+    for (r, row) in rows.iter().enumerate() {
+        if r > 0 {
+            text.push_str(ROW_SEPARATOR);
+        }
+
+        for (c, label) in row.iter().enumerate() {
+            if c > 0 {
+                text.push_str(CELL_SEPARATOR);
+            }
+
+            let start = text.len();
+            text.push_str(&label.text());
+            spans.push((label, start..text.len()));
+        }
+    }
+
+    (text, spans)
+}
+
+/// Converts a byte offset into `text` to a char offset, clamping it to the text.
+pub fn byte_to_char_offset(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    text[..offset].chars().count()
+}
+
+/// Converts a char offset into `text` to a byte offset, clamping it to the text.
+pub fn char_to_byte_offset(text: &str, offset: usize) -> usize {
+    text.char_indices().nth(offset).map_or(text.len(), |(i, _)| i)
+}
+
+/// Gets the text of a grid of labels, with cells separated by tabs and rows by newlines.
+pub fn grid_text(rows: &[Vec<gtk4::Label>]) -> String {
+    grid_spans(rows).0
+}
+
+/// Selects a byte range of the flattened text of a grid of labels, or clears the selection.
+pub fn grid_select(rows: &[Vec<gtk4::Label>], range: Option<Range<usize>>) {
+    let (_, spans) = grid_spans(rows);
+    for (label, span) in spans {
+        let selected = range.as_ref()
+            .map(|range| range.start.max(span.start)..range.end.min(span.end))
+            .filter(|selected| !selected.is_empty());
+
+        if let Some(selected) = selected {
+            let text = label.text();
+            let start = byte_to_char_offset(&text, selected.start - span.start);
+            let end = byte_to_char_offset(&text, selected.end - span.start);
+            label.select_region(start as i32, end as i32);
+        } else {
+            label.select_region(0, 0);
+        }
+    }
+}
+
+/// Gets the byte offset into the flattened text of a grid of labels that is
+/// closest to a point in the coordinates of `root`.
+pub fn grid_offset_at(root: &gtk4::Widget, rows: &[Vec<gtk4::Label>], x: f64, y: f64) -> Option<usize> {
+    let point = graphene::Point::new(x as f32, y as f32);
+    let (_, spans) = grid_spans(rows);
+
+    // Prefer the label under the point, falling back to the closest one.
+    let (label, span, local) = spans.into_iter()
+        .filter_map(|(label, span)| {
+            let local = root.compute_point(label, &point)?;
+            let width = label.width() as f32;
+            let height = label.height() as f32;
+            let dx = (-local.x()).max(local.x() - width).max(0.0);
+            let dy = (-local.y()).max(local.y() - height).max(0.0);
+            Some((dy, dx, label, span, local))
+        })
+        .min_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, _, label, span, local)| (label, span, local))?;
+
+    Some(span.start + label_offset_at(label, local.x().into(), local.y().into()))
+}
+
+/// Gets the byte offset into a label's text closest to a point in its coordinates.
+pub fn label_offset_at(label: &gtk4::Label, x: f64, y: f64) -> usize {
+    let layout = label.layout();
+    let (offset_x, offset_y) = label.layout_offsets();
+    let scale = f64::from(pango::SCALE);
+    let (_, index, trailing) = layout.xy_to_index(
+        ((x - f64::from(offset_x)) * scale) as i32,
+        ((y - f64::from(offset_y)) * scale) as i32,
+    );
+
+    let text = label.text();
+    let index = (index.max(0) as usize).min(text.len());
+    let trailing = text[index..].chars()
+        .take(trailing.max(0) as usize)
+        .map(char::len_utf8)
+        .sum::<usize>();
+
+    index + trailing
+}
@@ -0,0 +1,352 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use gtk4::prelude::*;
+use gtk4::{glib, TextIter, TextTag};
+use markdown::mdast::Node;
+
+use crate::blocks::{self, BlockWidget};
+use crate::inline::{InlineContent, InlineStyle};
+use crate::ir::RenderBuffer;
+use crate::view;
+
+const DEPTH_MULTIPLIER: i32 = 16;
+const CONTINUATION_INDENT: i32 = 16;
+const BLOCK_SPACING: i32 = 16;
+const SCRIPT_SCALE: f64 = 0.75;
+const SUPERSCRIPT_RISE: i32 = 4 * gtk4::pango::SCALE;
+const SUBSCRIPT_RISE: i32 = -2 * gtk4::pango::SCALE;
+const SMALL_SCALE: f64 = 0.83;
+
+const HEADING_SCALES: [(u8, f64); 6] = [
+    (1, 1.7),
+    (2, 1.5),
+    (3, 1.25),
+    (4, 1.1),
+    (5, 1.0),
+    (6, 0.9),
+];
+
+/// An embedded block widget, with the indentation of the block it was rendered from.
+type EmbeddedBlock = (Box<dyn BlockWidget>, i32);
+
+/// Renders a `RenderBuffer` into a single `TextView`.
+///
+/// Inline formatting is expressed with `TextTag`s, while blocks that can't be
+/// expressed as text, such as code blocks and tables, are embedded as block
+/// widgets at `TextChildAnchor`s.
+#[derive(Debug, Clone)]
+pub struct TextViewRenderer {
+    pub view: gtk4::TextView,
+    links: Rc<RefCell<Vec<(TextTag, String)>>>,
+    embedded: Rc<RefCell<Vec<EmbeddedBlock>>>,
+}
+
+impl Default for TextViewRenderer {
+    fn default() -> Self {
+        let view = gtk4::TextView::builder()
+            .css_classes(["cmark-textview"])
+            .editable(false)
+            .cursor_visible(true)
+            .wrap_mode(gtk4::WrapMode::WordChar)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+
+        let renderer = Self {
+            view,
+            links: Rc::new(RefCell::new(Vec::new())),
+            embedded: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        renderer.create_tags();
+        renderer.setup_links();
+        renderer.setup_embedded_width();
+        renderer
+    }
+}
+
+impl TextViewRenderer {
+    /// Lays out the whole render buffer into the text view, calling
+    /// `setup_block` on every embedded block after it is created.
+    pub fn render(&self, buffer: &RenderBuffer, setup_block: &dyn Fn(&dyn BlockWidget)) {
+        let text_buffer = self.view.buffer();
+        let tag_table = text_buffer.tag_table();
+
+        // Embedded widgets are removed along with their anchors.
+        text_buffer.set_text("");
+        self.embedded.borrow_mut().clear();
+        for (tag, _) in self.links.take() {
+            tag_table.remove(&tag);
+        }
+
+        let mut iter = text_buffer.end_iter();
+        for (i, block) in buffer.blocks.iter().enumerate() {
+            if i > 0 {
+                text_buffer.insert(&mut iter, "\n");
+            }
+
+            let start = iter.offset();
+            let mut indent = block.depth as i32 * DEPTH_MULTIPLIER;
+            if block.is_continuation {
+                indent += CONTINUATION_INDENT;
+            }
+
+            if let Some(marker) = &block.marker {
+                let indicator = format!("{} ", marker.label());
+                self.insert_with_tag_names(&mut iter, &indicator, &["cmark-marker"]);
+            }
+
+            match &block.node {
+                Node::Paragraph(paragraph) => {
+                    self.insert_content(&mut iter, &InlineContent::from_nodes(&paragraph.children), &[]);
+                },
+                Node::Heading(heading) => {
+                    let tag = tag_table.lookup(&format!("cmark-heading-{}", heading.depth.clamp(1, 6)));
+                    let tags = tag.into_iter().collect::<Vec<_>>();
+                    self.insert_content(&mut iter, &InlineContent::from_nodes(&heading.children), &tags);
+                },
+                node => self.insert_embedded(&mut iter, node, indent, setup_block),
+            }
+
+            let start = text_buffer.iter_at_offset(start);
+            text_buffer.apply_tag_by_name("cmark-block", &start, &iter);
+            if indent > 0 {
+                text_buffer.apply_tag(&self.indent_tag(indent), &start, &iter);
+            }
+        }
+    }
+
+    fn create_tags(&self) {
+        let tag_table = self.view.buffer().tag_table();
+        let tags = [
+            TextTag::builder().name("cmark-block").pixels_below_lines(BLOCK_SPACING).build(),
+            TextTag::builder().name("cmark-marker").build(),
+            TextTag::builder().name("cmark-emphasis").style(gtk4::pango::Style::Italic).build(),
+            TextTag::builder().name("cmark-strong").weight(700).build(),
+            TextTag::builder().name("cmark-delete").strikethrough(true).build(),
+            TextTag::builder().name("cmark-code").family("monospace").build(),
+            TextTag::builder()
+                .name("cmark-link")
+                .underline(gtk4::pango::Underline::Single)
+                .foreground("#3584e4")
+                .build(),
+            TextTag::builder().name("cmark-underline").underline(gtk4::pango::Underline::Single).build(),
+            TextTag::builder()
+                .name("cmark-kbd")
+                .family("monospace")
+                .background("rgba(128, 128, 128, 0.25)")
+                .build(),
+            TextTag::builder().name("cmark-sub").scale(SCRIPT_SCALE).rise(SUBSCRIPT_RISE).build(),
+            TextTag::builder().name("cmark-sup").scale(SCRIPT_SCALE).rise(SUPERSCRIPT_RISE).build(),
+            TextTag::builder().name("cmark-mark").background("#fff59d").foreground("#000000").build(),
+            TextTag::builder().name("cmark-small").scale(SMALL_SCALE).build(),
+            TextTag::builder()
+                .name("cmark-abbr")
+                .underline(gtk4::pango::Underline::Single)
+                .underline_rgba(&gtk4::gdk::RGBA::new(0.53, 0.53, 0.53, 1.0))
+                .build(),
+        ];
+
+        for tag in tags {
+            tag_table.add(&tag);
+        }
+
+        for (depth, scale) in HEADING_SCALES {
+            tag_table.add(&TextTag::builder()
+                .name(format!("cmark-heading-{}", depth))
+                .scale(scale)
+                .weight(700)
+                .build());
+        }
+    }
+
+    /// Opens links when they are clicked without selecting any text, or
+    /// when Enter is pressed with the cursor in them.
+    fn setup_links(&self) {
+        let click = gtk4::GestureClick::new();
+        let links = self.links.clone();
+
+        click.connect_released(move |gesture, _, x, y| {
+            let Some(view) = gesture.widget().and_downcast::<gtk4::TextView>() else {
+                return;
+            };
+
+            if view.buffer().has_selection() {
+                return;
+            }
+
+            let (x, y) = view.window_to_buffer_coords(gtk4::TextWindowType::Widget, x as i32, y as i32);
+            let Some(iter) = view.iter_at_location(x, y) else {
+                return;
+            };
+
+            if let Some(url) = link_at(&links.borrow(), &iter) {
+                view::activate_link(&view, &url);
+            }
+        });
+
+        let keys = gtk4::EventControllerKey::new();
+        let links = self.links.clone();
+
+        keys.connect_key_pressed(move |controller, key, _, _| {
+            let Some(view) = controller.widget().and_downcast::<gtk4::TextView>() else {
+                return glib::Propagation::Proceed;
+            };
+
+            if !matches!(key, gtk4::gdk::Key::Return | gtk4::gdk::Key::KP_Enter | gtk4::gdk::Key::ISO_Enter) {
+                return glib::Propagation::Proceed;
+            }
+
+            let buffer = view.buffer();
+            let cursor = buffer.iter_at_mark(&buffer.get_insert());
+            match link_at(&links.borrow(), &cursor) {
+                Some(url) => {
+                    view::activate_link(&view, &url);
+                    glib::Propagation::Stop
+                },
+                None => glib::Propagation::Proceed,
+            }
+        });
+
+        self.view.add_controller(click);
+        self.view.add_controller(keys);
+    }
+
+    /// Keeps embedded widgets as wide as the text view, since children at
+    /// anchors are otherwise only given their natural width.
+    ///
+    /// Text views don't notify about their size, and aren't necessarily in a
+    /// scrolled window, so the width is checked on every frame.
+    fn setup_embedded_width(&self) {
+        let embedded = self.embedded.clone();
+        let last_width = Cell::new(None);
+
+        self.view.add_tick_callback(move |view, _| {
+            let width = view.width();
+            if last_width.replace(Some(width)) != Some(width) {
+                for (block, indent) in embedded.borrow().iter() {
+                    let width = embedded_width(view, *indent);
+                    if block.root().width_request() != width {
+                        block.root().set_width_request(width);
+                    }
+                }
+            }
+
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn color_tag(&self, r: u16, g: u16, b: u16) -> TextTag {
+        let tag_table = self.view.buffer().tag_table();
+        let color = format!("#{:04x}{:04x}{:04x}", r, g, b);
+        let name = format!("cmark-color-{}", color);
+
+        tag_table.lookup(&name).unwrap_or_else(|| {
+            let tag = TextTag::builder().name(name).foreground(color).build();
+            tag_table.add(&tag);
+            tag
+        })
+    }
+
+    fn indent_tag(&self, indent: i32) -> TextTag {
+        let tag_table = self.view.buffer().tag_table();
+        let name = format!("cmark-indent-{}", indent);
+
+        tag_table.lookup(&name).unwrap_or_else(|| {
+            let tag = TextTag::builder().name(name).left_margin(indent).build();
+            tag_table.add(&tag);
+            tag
+        })
+    }
+
+    fn insert_with_tag_names(&self, iter: &mut TextIter, text: &str, names: &[&str]) {
+        let tag_table = self.view.buffer().tag_table();
+        let tags = names.iter()
+            .filter_map(|name| tag_table.lookup(name))
+            .collect::<Vec<_>>();
+
+        self.insert_with_tags(iter, text, &tags);
+    }
+
+    fn insert_with_tags(&self, iter: &mut TextIter, text: &str, tags: &[TextTag]) {
+        let tags = tags.iter().collect::<Vec<_>>();
+        self.view.buffer().insert_with_tags(iter, text, &tags);
+    }
+
+    /// Inserts inline content, applying a tag for each of its styled spans.
+    fn insert_content(&self, iter: &mut TextIter, content: &InlineContent, tags: &[TextTag]) {
+        let text_buffer = self.view.buffer();
+        let tag_table = text_buffer.tag_table();
+        let start = iter.offset();
+        self.insert_with_tags(iter, &content.text, tags);
+
+        let char_offset = |byte: usize| start + content.text[..byte].chars().count() as i32;
+        for span in &content.spans {
+            let tags = match &span.style {
+                InlineStyle::Emphasis => vec![tag_table.lookup("cmark-emphasis")],
+                InlineStyle::Strong => vec![tag_table.lookup("cmark-strong")],
+                InlineStyle::Delete => vec![tag_table.lookup("cmark-delete")],
+                InlineStyle::Code => vec![tag_table.lookup("cmark-code")],
+                InlineStyle::Link(url) => {
+                    let link_tag = TextTag::new(None);
+                    tag_table.add(&link_tag);
+                    self.links.borrow_mut().push((link_tag.clone(), url.clone()));
+                    vec![tag_table.lookup("cmark-link"), Some(link_tag)]
+                },
+                InlineStyle::Underline => vec![tag_table.lookup("cmark-underline")],
+                InlineStyle::Keyboard => vec![tag_table.lookup("cmark-kbd")],
+                InlineStyle::Subscript => vec![tag_table.lookup("cmark-sub")],
+                InlineStyle::Superscript => vec![tag_table.lookup("cmark-sup")],
+                InlineStyle::Mark => vec![tag_table.lookup("cmark-mark")],
+                InlineStyle::Small => vec![tag_table.lookup("cmark-small")],
+                InlineStyle::Abbreviation(_) => vec![tag_table.lookup("cmark-abbr")],
+                InlineStyle::Foreground(r, g, b) => vec![Some(self.color_tag(*r, *g, *b))],
+            };
+
+            let span_start = text_buffer.iter_at_offset(char_offset(span.range.start));
+            let span_end = text_buffer.iter_at_offset(char_offset(span.range.end));
+            for tag in tags.into_iter().flatten() {
+                text_buffer.apply_tag(&tag, &span_start, &span_end);
+            }
+        }
+    }
+
+    fn insert_embedded(
+        &self,
+        iter: &mut TextIter,
+        node: &Node,
+        indent: i32,
+        setup_block: &dyn Fn(&dyn BlockWidget),
+    ) {
+        let Some(mut block) = blocks::create_block_widget(node) else {
+            return;
+        };
+
+        block.update(node);
+        setup_block(block.as_ref());
+
+        block.root().set_width_request(embedded_width(&self.view, indent));
+
+        let anchor = self.view.buffer().create_child_anchor(iter);
+        self.view.add_child_at_anchor(block.root(), &anchor);
+        self.embedded.borrow_mut().push((block, indent));
+    }
+}
+
+/// Gets the URL of the link at an iterator, if any.
+fn link_at(links: &[(TextTag, String)], iter: &TextIter) -> Option<String> {
+    links.iter()
+        .find(|(tag, _)| iter.has_tag(tag))
+        .map(|(_, url)| url.clone())
+}
+
+/// Gets the width of an embedded widget at an indentation, or -1 for its
+/// natural width before the text view is allocated.
+fn embedded_width(view: &gtk4::TextView, indent: i32) -> i32 {
+    let width = view.width() - view.left_margin() - view.right_margin();
+    if width > 0 {
+        (width - indent).max(0)
+    } else {
+        -1
+    }
+}
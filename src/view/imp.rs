@@ -1,34 +1,72 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 use std::rc::Rc;
-use std::sync::LazyLock;
+use std::sync::OnceLock;
+use futures_signals::signal::Mutable;
+use gtk4::gdk;
 use gtk4::glib::{self, Properties};
+use gtk4::glib::subclass::Signal;
 use gtk4::subclass::prelude::*;
 use gtk4::prelude::*;
 use markdown::ParseOptions;
+use markdown::mdast::Node;
 
+use crate::backend::ParserBackend;
+use crate::find::FindOptions;
+use crate::frontmatter::{FrontMatter, FrontMatterDisplay};
+use crate::highlight::{CodeTheme, HighlightBackend};
+use crate::html::{HtmlPolicy, InlineHtmlPolicy};
+use crate::images::ImageResolver;
+use crate::textview::TextViewRenderer;
 use crate::util::get_widget_children;
-use super::MarkdownBlock;
-use super::super::ir::{RenderBuffer, RenderBlock};
-use super::super::blocks::{
-    BlockWidgetFactory,
-    TextBlockFactory,
-    TableBlockFactory,
-    ThematicBreakBlockFactory,
-    CodeBlock, CodeBlockFactory,
-};
+use super::{MarkdownBlock, ParserFlavor, RenderMode};
+use super::super::ir::{AlertKind, RenderBuffer, RenderBlock, RenderOptions};
+use super::super::blocks::{self, BlockWidget, CodeBlock, DetailsBlock, ImageBlock};
 
 const DEPTH_MULTIPLIER: i32 = 16;
 const MARKER_SPACING: i32 = 4;
-
-static FACTORIES: LazyLock<Vec<Box<dyn BlockWidgetFactory + Send + Sync>>> = LazyLock::new(|| vec![
-    Box::new(TextBlockFactory),
-    Box::new(CodeBlockFactory),
-    Box::new(TableBlockFactory),
-    Box::new(ThematicBreakBlockFactory),
-]);
+const DEFAULT_CSS: &str = include_str!("style.css");
 
 type CodeBlockCallback = Box<dyn Fn(&CodeBlock)>;
+type Transform = Box<dyn Fn(&mut Node)>;
+
+/// The state of an active find in the view.
+#[derive(Debug, Default)]
+pub(super) struct FindState {
+    pub query: String,
+    pub options: FindOptions,
+    /// Block indices and their match counts, in document order.
+    pub counts: Vec<(usize, usize)>,
+    pub current: Option<usize>,
+}
+
+/// A position in the view's text, as a block index and a byte offset into its text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct SelectionPoint {
+    pub block: usize,
+    pub offset: usize,
+}
+
+/// A selection spanning one or more blocks.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ViewSelection {
+    pub anchor: SelectionPoint,
+    pub focus: SelectionPoint,
+}
+
+impl ViewSelection {
+    /// Returns the selection's endpoints in document order.
+    pub fn ordered(&self) -> (SelectionPoint, SelectionPoint) {
+        (self.anchor.min(self.focus), self.anchor.max(self.focus))
+    }
+}
+
+impl FindState {
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+}
 
 #[derive(Default, Properties)]
 #[properties(wrapper_type = super::MarkdownView)]
@@ -36,9 +74,59 @@ pub struct MarkdownView {
     pub(super) buffer: Rc<RefCell<RenderBuffer>>,
     pub(super) blocks: Rc<RefCell<HashMap<usize, MarkdownBlock>>>,
     pub(super) code_block_callback: Rc<RefCell<Option<CodeBlockCallback>>>,
+    pub(super) find: RefCell<Option<FindState>>,
+    pub(super) selection: Cell<Option<ViewSelection>>,
+    drag_anchor: Cell<Option<SelectionPoint>>,
+    pub(super) text_view: RefCell<Option<TextViewRenderer>>,
+    /// The last rendered AST, before any transforms, so option changes don't
+    /// need a reparse.
+    mdast: RefCell<Option<Node>>,
+    /// Whether the content is a tree from `set_mdast` rather than the
+    /// `markdown` property, which then isn't the source of what's shown.
+    pub(super) shows_mdast: Cell<bool>,
+    pub(super) transforms: RefCell<Vec<Transform>>,
+    pub(super) front_matter: Mutable<Option<FrontMatter>>,
+    /// The options parsed with when the flavor is `ParserFlavor::Custom`.
+    pub(super) custom_parse_options: RefCell<Option<ParseOptions>>,
+    /// The parser used instead of the `markdown` crate, if any.
+    pub(super) backend: RefCell<Option<Box<dyn ParserBackend>>>,
+    /// Resolves `<img>` sources, images aren't loaded without one.
+    image_resolver: RefCell<Option<ImageResolver>>,
 
     #[property(get, set)]
     markdown: Rc<RefCell<String>>,
+
+    /// Which markdown syntax the content is parsed as.
+    #[property(get, set, builder(ParserFlavor::default()))]
+    flavor: Cell<ParserFlavor>,
+
+    /// Whether adjacent paragraphs are merged into a single label.
+    #[property(get, set)]
+    merge_paragraphs: Cell<bool>,
+
+    /// How the content is laid out.
+    #[property(get, set, builder(RenderMode::default()))]
+    render_mode: Cell<RenderMode>,
+
+    /// How inline HTML outside of the supported subset is displayed.
+    #[property(get, set, builder(InlineHtmlPolicy::default()))]
+    inline_html_policy: Cell<InlineHtmlPolicy>,
+
+    /// How block-level HTML is displayed.
+    #[property(get, set, builder(HtmlPolicy::default()))]
+    html_policy: Cell<HtmlPolicy>,
+
+    /// How front matter is displayed.
+    #[property(get, set, builder(FrontMatterDisplay::default()))]
+    front_matter_display: Cell<FrontMatterDisplay>,
+
+    /// Which syntax highlighter colors code blocks.
+    #[property(get, set, builder(HighlightBackend::default()))]
+    highlight_backend: Cell<HighlightBackend>,
+
+    /// The color theme of code blocks.
+    #[property(get, set, builder(CodeTheme::default()))]
+    code_theme: Cell<CodeTheme>,
 }
 
 #[glib::object_subclass]
@@ -50,15 +138,49 @@ impl ObjectSubclass for MarkdownView {
 
 #[glib::derived_properties]
 impl ObjectImpl for MarkdownView {
+    fn signals() -> &'static [Signal] {
+        static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+        SIGNALS.get_or_init(|| vec![
+            // Emitted when a link is clicked or activated with the keyboard.
+            // Handlers return `true` to stop the link from being opened.
+            Signal::builder("link-activated")
+                .param_types([String::static_type()])
+                .return_type::<bool>()
+                .accumulator(|_, _, value| if value.get::<bool>().unwrap_or(false) {
+                    ControlFlow::Break(value.clone())
+                } else {
+                    ControlFlow::Continue(value.clone())
+                })
+                .build(),
+        ])
+    }
+
     fn constructed(&self) {
         self.parent_constructed();
         self.obj().set_orientation(gtk4::Orientation::Vertical);
-        self.obj().set_spacing(16);
+        self.obj().add_css_class("cmark-view");
+        add_default_style(&self.obj().display());
 
         self.obj().connect_markdown_notify(|view| {
-            let markdown = view.markdown();
-            view.imp().render(&markdown);
+            view.imp().shows_mdast.set(false);
+            view.imp().reparse();
+        });
+
+        self.obj().connect_flavor_notify(|view| view.imp().reparse());
+
+        self.obj().connect_merge_paragraphs_notify(|view| view.imp().rerender());
+        self.obj().connect_inline_html_policy_notify(|view| view.imp().rerender());
+        self.obj().connect_html_policy_notify(|view| view.imp().rerender());
+        self.obj().connect_front_matter_display_notify(|view| view.imp().rerender());
+        self.obj().connect_highlight_backend_notify(|view| view.imp().update_highlighting());
+        self.obj().connect_code_theme_notify(|view| view.imp().update_highlighting());
+
+        self.obj().connect_render_mode_notify(|view| {
+            view.imp().clear_rendered();
+            view.imp().rerender();
         });
+
+        self.setup_selection();
     }
 }
 
@@ -81,7 +203,20 @@ impl MarkdownView {
         &self,
         markdown: &str,
     ) {
-        let mdast = match markdown::to_mdast(markdown, &ParseOptions::gfm()) {
+        let parsed = if let Some(backend) = self.backend.borrow().as_ref() {
+            backend.parse(markdown)
+        } else {
+            match self.flavor.get().parse_options() {
+                Some(options) => markdown::to_mdast(markdown, &options),
+                None => {
+                    let custom = self.custom_parse_options.borrow();
+                    let default = ParseOptions::default();
+                    markdown::to_mdast(markdown, custom.as_ref().unwrap_or(&default))
+                },
+            }.map_err(|err| err.to_string())
+        };
+
+        let mdast = match parsed {
             Ok(mdast) => mdast,
             Err(err) => {
                 eprintln!("Failed to parse markdown: {}", err);
@@ -89,7 +224,50 @@ impl MarkdownView {
             }
         };
 
-        self.buffer.borrow_mut().set(&mdast);
+        self.render_mdast(mdast);
+    }
+
+    /// Parses and renders the `markdown` property again, e.g. after the
+    /// parser changed. A tree from `set_mdast` is rendered again instead,
+    /// since there's no source to parse.
+    pub(super) fn reparse(&self) {
+        if self.shows_mdast.get() {
+            self.rerender();
+        } else {
+            self.render(&self.obj().markdown());
+        }
+    }
+
+    /// Renders the last rendered AST again, e.g. after an option changed.
+    pub(super) fn rerender(&self) {
+        let mdast = self.mdast.borrow().clone();
+        if let Some(mdast) = mdast {
+            self.render_mdast(mdast);
+        }
+    }
+
+    pub(super) fn render_mdast(&self, mdast: Node) {
+        let mut transformed = mdast.clone();
+        for transform in self.transforms.borrow().iter() {
+            transform(&mut transformed);
+        }
+
+        // Front matter can only be the first node of the document.
+        let front_matter = transformed.children()
+            .and_then(|children| children.first())
+            .and_then(FrontMatter::from_node);
+        self.front_matter.set_neq(front_matter);
+
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.options = self.render_options();
+        buffer.set(&transformed);
+        drop(buffer);
+        self.mdast.replace(Some(mdast));
+
+        if self.render_mode.get() == RenderMode::TextView {
+            self.render_text_view();
+            return;
+        }
 
         // Only remove widgets where their index is above blocks.len and it makes
         // sense to leave in the root, rest of the widgets will be reused.
@@ -97,7 +275,7 @@ impl MarkdownView {
         self.blocks.borrow_mut().retain(|i, block| {
             let buffer_blocks = &self.buffer.borrow().blocks;
             let valid_node = if let Some(buffer_block) = buffer_blocks.get(*i) {
-                block.block.valid_node(&buffer_block.node)
+                block.block.valid_node(&buffer_block.node) && block.fits_marker(buffer_block.marker.as_ref())
             } else {
                 false
             };
@@ -121,6 +299,313 @@ impl MarkdownView {
                 }
             }
         }
+
+        self.refresh_find();
+        self.apply_selection();
+    }
+
+    /// Gets the options for flattening the AST from the view's properties.
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            merge_paragraphs: self.merge_paragraphs.get(),
+            inline_html: self.inline_html_policy.get(),
+            html: self.html_policy.get(),
+            front_matter: self.front_matter_display.get(),
+        }
+    }
+
+    /// Lays out the render buffer into a single `TextView`.
+    fn render_text_view(&self) {
+        let renderer = self.text_view.borrow_mut()
+            .get_or_insert_with(|| {
+                let renderer = TextViewRenderer::default();
+                self.obj().append(&renderer.view);
+                renderer
+            })
+            .clone();
+
+        let setup_block = |block: &dyn BlockWidget| {
+            self.configure_block(block);
+            if let Some(callback) = self.code_block_callback.borrow().as_deref()
+                && let Some(code_block) = block.downcast_ref::<CodeBlock>()
+            {
+                callback(code_block);
+            }
+        };
+
+        renderer.render(&self.buffer.borrow(), &setup_block);
+    }
+
+    /// Applies the highlighting options to every code block.
+    fn update_highlighting(&self) {
+        if self.render_mode.get() == RenderMode::TextView {
+            self.rerender();
+            return;
+        }
+
+        for block in self.blocks.borrow().values() {
+            if let Some(code_block) = block.block.downcast_ref::<CodeBlock>() {
+                code_block.set_highlighting(self.highlight_backend.get(), self.code_theme.get());
+            }
+        }
+    }
+
+    /// Sets the resolver `<img>` sources are loaded through, in this view and
+    /// the views nested in its blocks.
+    pub(crate) fn set_image_resolver(&self, resolver: Option<ImageResolver>) {
+        self.image_resolver.replace(resolver);
+        if self.render_mode.get() == RenderMode::TextView {
+            self.rerender();
+            return;
+        }
+
+        for block in self.blocks.borrow().values() {
+            self.configure_block(block.block.as_ref());
+        }
+    }
+
+    pub(super) fn image_resolver(&self) -> Option<ImageResolver> {
+        self.image_resolver.borrow().clone()
+    }
+
+    /// Passes the view's options that aren't part of the render buffer down
+    /// to a new block.
+    fn configure_block(&self, block: &dyn BlockWidget) {
+        if let Some(code_block) = block.downcast_ref::<CodeBlock>() {
+            code_block.set_highlighting(self.highlight_backend.get(), self.code_theme.get());
+        } else if let Some(image) = block.downcast_ref::<ImageBlock>() {
+            image.set_resolver(self.image_resolver.borrow().clone());
+        } else if let Some(details) = block.downcast_ref::<DetailsBlock>() {
+            details.set_image_resolver(self.image_resolver.borrow().clone());
+        }
+    }
+
+    /// Removes everything rendered so far, e.g. when switching render modes.
+    fn clear_rendered(&self) {
+        self.obj().unselect_all();
+        self.obj().clear_find();
+        self.blocks.borrow_mut().clear();
+        self.text_view.replace(None);
+
+        for child in self.children() {
+            self.obj().remove(&child);
+        }
+    }
+
+    /// Sets up dragging a selection across blocks and the selection shortcuts.
+    fn setup_selection(&self) {
+        // Drags within a single block are left to the block's own widgets, the
+        // gesture only claims the sequence once it crosses into another block.
+        let drag = gtk4::GestureDrag::new();
+        drag.set_propagation_phase(gtk4::PropagationPhase::Capture);
+
+        drag.connect_drag_begin(glib::clone!(
+            #[weak(rename_to = view)] self.obj(),
+            move |_, x, y| {
+                let imp = view.imp();
+                view.unselect_all();
+                imp.drag_anchor.set(imp.selection_point_at(x, y));
+            }
+        ));
+
+        drag.connect_drag_update(glib::clone!(
+            #[weak(rename_to = view)] self.obj(),
+            move |gesture, dx, dy| {
+                let imp = view.imp();
+                let Some(anchor) = imp.drag_anchor.get() else {
+                    return;
+                };
+                let Some((x, y)) = gesture.start_point() else {
+                    return;
+                };
+                let Some(focus) = imp.selection_point_at(x + dx, y + dy) else {
+                    return;
+                };
+
+                if imp.selection.get().is_none() && focus.block == anchor.block {
+                    return;
+                }
+
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                imp.selection.set(Some(ViewSelection { anchor, focus }));
+                imp.apply_selection();
+            }
+        ));
+
+        drag.connect_drag_end(glib::clone!(
+            #[weak(rename_to = view)] self.obj(),
+            move |_, _, _| view.imp().drag_anchor.set(None)
+        ));
+
+        self.obj().add_controller(drag);
+
+        // Text views and entries select their own content, as does the single
+        // text view of the TextView render mode.
+        let select_all = gtk4::CallbackAction::new(|widget, _| {
+            let Some(view) = widget.downcast_ref::<super::MarkdownView>() else {
+                return glib::Propagation::Proceed;
+            };
+
+            let focus_selects = view.root()
+                .and_then(|root| root.focus())
+                .is_some_and(|focus| focus.is::<gtk4::TextView>() || focus.is::<gtk4::Editable>());
+
+            if view.render_mode() == RenderMode::TextView || focus_selects {
+                glib::Propagation::Proceed
+            } else {
+                view.select_all();
+                glib::Propagation::Stop
+            }
+        });
+
+        // Only take over copying when the selection spans blocks, so copying
+        // a selection within a single block keeps its native behavior.
+        let copy = gtk4::CallbackAction::new(|widget, _| {
+            if let Some(view) = widget.downcast_ref::<super::MarkdownView>()
+                && view.imp().selection.get().is_some()
+            {
+                view.copy_selection();
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        });
+
+        let shortcuts = gtk4::ShortcutController::new();
+        shortcuts.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        shortcuts.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("<Control>a"),
+            Some(select_all),
+        ));
+        shortcuts.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("<Control>c"),
+            Some(copy),
+        ));
+        self.obj().add_controller(shortcuts);
+    }
+
+    /// Gets the blocks sorted by index.
+    pub(super) fn sorted_blocks(&self) -> Vec<(usize, MarkdownBlock)> {
+        let mut blocks = self.blocks.borrow().iter()
+            .map(|(i, block)| (*i, block.clone()))
+            .collect::<Vec<_>>();
+
+        blocks.sort_unstable_by_key(|(i, _)| *i);
+        blocks
+    }
+
+    /// Gets the selection point closest to a point in the view's coordinates.
+    fn selection_point_at(&self, x: f64, y: f64) -> Option<SelectionPoint> {
+        let view = self.obj();
+        let blocks = self.sorted_blocks();
+
+        // The block under the point, or the last block above it.
+        let (block, md_block) = blocks.iter()
+            .take_while(|(_, block)| block.root.compute_bounds(&*view)
+                .is_none_or(|bounds| f64::from(bounds.y()) <= y))
+            .last()
+            .or_else(|| blocks.first())?;
+
+        let point = gtk4::graphene::Point::new(x as f32, y as f32);
+        let local = view.compute_point(md_block.block.root(), &point)?;
+        let offset = md_block.block.offset_at(local.x().into(), local.y().into())?;
+
+        Some(SelectionPoint {
+            block: *block,
+            offset,
+        })
+    }
+
+    /// Applies the view's selection to the blocks it spans.
+    pub(super) fn apply_selection(&self) {
+        let Some(selection) = self.selection.get() else {
+            return;
+        };
+
+        let (start, end) = selection.ordered();
+        for (i, block) in self.sorted_blocks() {
+            let range = if i < start.block || i > end.block {
+                None
+            } else {
+                let from = if i == start.block { start.offset } else { 0 };
+                let to = if i == end.block { end.offset } else { usize::MAX };
+                Some(from..to)
+            };
+
+            block.block.select(range);
+        }
+    }
+
+    /// Re-runs the active find, if any, so highlights follow content changes.
+    pub(super) fn refresh_find(&self) {
+        let mut find = self.find.borrow_mut();
+        let Some(state) = find.as_mut() else {
+            return;
+        };
+
+        let blocks = self.blocks.borrow();
+        let mut indices = blocks.keys().copied().collect::<Vec<_>>();
+        indices.sort_unstable();
+
+        state.counts = indices.into_iter()
+            .map(|i| (i, blocks[&i].block.find(&state.query, &state.options)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+
+        let total = state.total();
+        state.current = state.current.filter(|current| *current < total);
+        let current = state.current;
+        drop(blocks);
+        drop(find);
+
+        if let Some(current) = current {
+            self.select_match(current, false);
+        }
+    }
+
+    /// Marks a match as the current one, optionally scrolling it into view.
+    pub(super) fn select_match(&self, index: usize, scroll: bool) {
+        let find = self.find.borrow();
+        let Some(state) = find.as_ref() else {
+            return;
+        };
+
+        let mut remaining = index;
+        for (block_index, count) in &state.counts {
+            if remaining >= *count {
+                remaining -= count;
+                continue;
+            }
+
+            let blocks = self.blocks.borrow();
+            let Some(block) = blocks.get(block_index) else {
+                return;
+            };
+
+            let widget = block.block.select_match(remaining);
+            if scroll && let Some(widget) = widget {
+                self.scroll_to_widget(&widget);
+            }
+            return;
+        }
+    }
+
+    /// Scrolls the nearest enclosing `ScrolledWindow` so the widget is centered.
+    fn scroll_to_widget(&self, widget: &gtk4::Widget) {
+        let Some(window) = self.obj().ancestor(gtk4::ScrolledWindow::static_type())
+            .and_then(|window| window.downcast::<gtk4::ScrolledWindow>().ok())
+        else {
+            return;
+        };
+
+        let Some(bounds) = widget.compute_bounds(&window) else {
+            return;
+        };
+
+        let adjustment = window.vadjustment();
+        let y = f64::from(bounds.y()) + adjustment.value();
+        let height = f64::from(bounds.height());
+        adjustment.set_value(y - (adjustment.page_size() - height) / 2.0);
     }
 
     fn get_continuation_depth(
@@ -157,19 +642,19 @@ impl MarkdownView {
     ) -> Option<(MarkdownBlock, bool)> {
         if let Some(text_block) = self.blocks.borrow_mut().get_mut(&i)
             && text_block.block.valid_node(&block.node)
+            && text_block.fits_marker(block.marker.as_ref())
         {
             text_block.block.update(&block.node);
+            text_block.set_marker(block.marker.as_ref());
             return Some((text_block.clone(), true));
         }
 
-        FACTORIES.iter()
-            .find(|f| f.matches(&block.node))
+        blocks::create_block_widget(&block.node)
             .map_or_else(|| {
                 eprintln!("No factory found for node: {:?}", block.node);
                 None
             }, 
-            |factory| {
-                let block_widget = factory.create();
+            |block_widget| {
                 let markdown_block = MarkdownBlock::new(block_widget, block.marker.as_ref());
                 self.blocks.borrow_mut().insert(i, markdown_block.clone());
                 Some((markdown_block, false))
@@ -186,18 +671,71 @@ impl MarkdownView {
         let continuation_depth = Self::get_continuation_depth(i, render_block, children);
 
         block.root.set_margin_start((render_block.depth as i32 * DEPTH_MULTIPLIER) + continuation_depth);
+        Self::set_alert_classes(&block.root, render_block.alert);
 
         if !reused {
+            if let Some(details) = block.block.downcast_ref::<DetailsBlock>() {
+                details.bind_options(&self.obj());
+            }
+            self.configure_block(block.block.as_ref());
+
             if let Some(code_block_callback) = self.code_block_callback.borrow().as_ref()
                 && let Some(code_block) = block.block.downcast_ref::<CodeBlock>()
             {
                 code_block_callback(code_block);
-                self.obj().append(&code_block.container);
+                self.insert_block_root(i, &code_block.container);
             } else {
-                self.obj().append(&block.root);
+                self.insert_block_root(i, &block.root);
             }
         }
 
         Some(block)
     }
-}
\ No newline at end of file
+
+    /// Marks a block as part of an alert, e.g. `cmark-alert cmark-alert-note`.
+    fn set_alert_classes(root: &gtk4::Widget, alert: Option<AlertKind>) {
+        for class in root.css_classes() {
+            if class.starts_with("cmark-alert") {
+                root.remove_css_class(&class);
+            }
+        }
+
+        if let Some(alert) = alert {
+            root.add_css_class("cmark-alert");
+            root.add_css_class(&format!("cmark-alert-{}", alert.name()));
+        }
+    }
+
+    /// Inserts a block's root widget right after the block preceding it, so
+    /// replaced blocks keep their place instead of moving to the end.
+    fn insert_block_root(&self, i: usize, root: &impl IsA<gtk4::Widget>) {
+        let previous = i.checked_sub(1)
+            .and_then(|previous| self.blocks.borrow().get(&previous).map(|block| block.root.clone()));
+
+        self.obj().insert_child_after(root, previous.as_ref());
+    }
+}
+
+/// Adds the default stylesheet to `display` the first time a view is created
+/// on it, at fallback priority so application styles override it.
+fn add_default_style(display: &gdk::Display) {
+    thread_local! {
+        static STYLED_DISPLAYS: RefCell<Vec<glib::WeakRef<gdk::Display>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    STYLED_DISPLAYS.with_borrow_mut(|displays| {
+        displays.retain(|styled| styled.upgrade().is_some());
+        if displays.iter().any(|styled| styled.upgrade().as_ref() == Some(display)) {
+            return;
+        }
+
+        let provider = gtk4::CssProvider::new();
+        provider.load_from_data(DEFAULT_CSS);
+        gtk4::style_context_add_provider_for_display(
+            display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_FALLBACK,
+        );
+        displays.push(display.downgrade());
+    });
+}
@@ -1,14 +1,75 @@
 mod imp;
 
+use std::cell::RefCell;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use futures_signals::signal::Mutable;
 use gtk4::glib::subclass::types::ObjectSubclassIsExt as _;
 use gtk4::prelude::*;
+use gtk4::{cairo, gdk, gio};
 use gtk4::glib::{self, Object};
 
+use crate::backend::ParserBackend;
 use crate::ir::RenderMarker;
 use crate::blocks::{BlockWidget, CodeBlock};
+use crate::export::PrintLayout;
+use crate::find::FindOptions;
+use crate::frontmatter::FrontMatter;
+use crate::images;
 
 const MARKER_SPACING: i32 = 4;
 
+/// How a `MarkdownView` lays out its content.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkRenderMode")]
+pub enum RenderMode {
+    /// One widget per block, reused across renders.
+    #[default]
+    Blocks,
+    /// A single `TextView` with tags for inline formatting and embedded
+    /// widgets for code blocks and tables. This gives native selection and
+    /// cursor navigation, but lays out the whole document on every render.
+    ///
+    /// Find, the cross-block selection model and source position lookups
+    /// only apply to the `Blocks` mode.
+    TextView,
+}
+
+/// Which markdown syntax the content is parsed as.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GtkCmarkParserFlavor")]
+pub enum ParserFlavor {
+    /// Strict CommonMark, without autolinks, tables or front matter.
+    CommonMark,
+    /// GitHub flavored markdown, with front matter.
+    #[default]
+    Gfm,
+    /// GitHub flavored markdown with `$` and `$$` math, and front matter.
+    GfmMath,
+    /// The options given to `MarkdownView::set_parse_options`.
+    Custom,
+}
+
+impl ParserFlavor {
+    /// Gets the parse options for the flavor, or `None` for `Custom`.
+    pub fn parse_options(self) -> Option<markdown::ParseOptions> {
+        let mut options = match self {
+            Self::CommonMark => return Some(markdown::ParseOptions::default()),
+            Self::Gfm | Self::GfmMath => markdown::ParseOptions::gfm(),
+            Self::Custom => return None,
+        };
+
+        options.constructs.frontmatter = true;
+        if self == Self::GfmMath {
+            options.constructs.math_flow = true;
+            options.constructs.math_text = true;
+        }
+
+        Some(options)
+    }
+}
+
 glib::wrapper! {
     pub struct MarkdownView(ObjectSubclass<imp::MarkdownView>)
         @extends gtk4::Widget, gtk4::Box,
@@ -22,6 +83,22 @@ impl Default for MarkdownView {
 }
 
 impl MarkdownView {
+    /// Connects to `link-activated`, which is emitted when a link is clicked
+    /// or activated with the keyboard.
+    ///
+    /// Returning `Propagation::Stop` keeps the link from being opened with
+    /// the default handler, e.g. to navigate within the app instead. Links in
+    /// nested views, like those of `<details>` sections, are passed on to the
+    /// outer views until a handler stops them.
+    pub fn connect_link_activated<F>(&self, callback: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str) -> glib::Propagation + 'static,
+    {
+        self.connect_closure("link-activated", false, glib::closure_local!(
+            move |view: &Self, uri: String| -> bool { callback(view, &uri).into() }
+        ))
+    }
+
     /// Sets the function that is run before a new code block is appended.
     pub fn set_code_block_callback<F>(&self, callback: F)
     where
@@ -30,27 +107,515 @@ impl MarkdownView {
         let imp = self.imp();
         *imp.code_block_callback.borrow_mut() = Some(Box::new(callback));
     }
+
+    /// Loads the images of `<img>` elements through `resolver`, which maps
+    /// their `src` to a file, or `None` to not load it.
+    ///
+    /// No images are loaded by default. `data:` URIs are decoded once a
+    /// resolver is set, and the resolver may return `file`, `resource`,
+    /// `http` or `https` URIs. Images are loaded asynchronously, except when
+    /// printing or exporting, where remote images aren't loaded.
+    pub fn set_image_resolver<F>(&self, resolver: F)
+    where
+        F: Fn(&str) -> Option<gio::File> + 'static,
+    {
+        self.imp().set_image_resolver(Some(Rc::new(resolver)));
+    }
+
+    /// Loads the images of `<img>` elements with relative paths from within
+    /// `dir`, e.g. the directory of the markdown file.
+    pub fn set_image_base_dir(&self, dir: impl Into<PathBuf>) {
+        self.imp().set_image_resolver(Some(images::base_dir_resolver(dir.into())));
+    }
+
+    /// Stops loading images, leaving their alternative text.
+    pub fn clear_image_resolver(&self) {
+        self.imp().set_image_resolver(None);
+    }
+
+    /// Parses the content with the given options, switching the flavor to
+    /// `ParserFlavor::Custom`.
+    pub fn set_parse_options(&self, options: markdown::ParseOptions) {
+        self.imp().custom_parse_options.replace(Some(options));
+        if self.flavor() == ParserFlavor::Custom {
+            self.imp().reparse();
+        } else {
+            self.set_flavor(ParserFlavor::Custom);
+        }
+    }
+
+    /// Parses the content with another backend instead of the `markdown`
+    /// crate, ignoring the `flavor`. Passing `None` goes back to the
+    /// `markdown` crate.
+    pub fn set_parser_backend(&self, backend: Option<Box<dyn ParserBackend>>) {
+        self.imp().backend.replace(backend);
+        self.imp().reparse();
+    }
+
+    /// Serializes the rendered document to HTML.
+    ///
+    /// The HTML is generated from the same blocks the view shows, with the
+    /// CSS classes of their widgets, so a stylesheet can mirror the theme.
+    pub fn to_html(&self) -> String {
+        crate::export::to_html(&self.imp().buffer.borrow())
+    }
+
+    /// Renders the document as plain text wrapped to `width` columns.
+    ///
+    /// Lists keep their markers, tables are drawn with box-drawing
+    /// characters, and link targets follow their text.
+    pub fn to_plain_text(&self, width: usize) -> String {
+        crate::export::to_plain_text(&self.imp().buffer.borrow(), width)
+    }
+
+    /// Renders the document like `to_plain_text`, styled with ANSI escape
+    /// sequences for terminals.
+    pub fn to_ansi(&self, width: usize) -> String {
+        crate::export::to_ansi(&self.imp().buffer.borrow(), width)
+    }
+
+    /// Shows the print dialog for the rendered document.
+    ///
+    /// Blocks are paginated so that headings stay with the content after
+    /// them, and tables break between rows with their header repeated.
+    pub fn print(&self) -> Result<gtk4::PrintOperationResult, glib::Error> {
+        let operation = gtk4::PrintOperation::new();
+        operation.set_unit(gtk4::Unit::Points);
+        let layout = Rc::new(RefCell::new(None::<PrintLayout>));
+
+        operation.connect_begin_print(glib::clone!(
+            #[weak(rename_to = view)] self,
+            #[strong] layout,
+            move |operation, context| {
+                let print_layout = PrintLayout::new(
+                    &view.imp().buffer.borrow(),
+                    &context.create_pango_context(),
+                    view.imp().image_resolver().as_ref(),
+                    context.width(),
+                    context.height(),
+                );
+
+                operation.set_n_pages(print_layout.n_pages() as i32);
+                layout.replace(Some(print_layout));
+            }
+        ));
+
+        operation.connect_draw_page(glib::clone!(
+            #[strong] layout,
+            move |_, context, page| if let Some(layout) = layout.borrow().as_ref() {
+                layout.draw_page(&context.cairo_context(), page as usize);
+            }
+        ));
+
+        let window = self.root().and_downcast::<gtk4::Window>();
+        operation.run(gtk4::PrintOperationAction::PrintDialog, window.as_ref())
+    }
+
+    /// Exports the rendered document to a PDF file with A4 pages.
+    pub fn export_pdf(&self, path: impl AsRef<Path>) -> Result<(), cairo::Error> {
+        let imp = self.imp();
+        crate::export::write_pdf(&imp.buffer.borrow(), imp.image_resolver().as_ref(), path)
+    }
+
+    /// Rasterizes the rendered document at a width in pixels, e.g. for
+    /// thumbnails.
+    ///
+    /// This lays the document out offscreen the same way as printing does,
+    /// and draws it with Cairo's software rasterizer, so it doesn't need the
+    /// view to be shown or a GPU. Styling from CSS isn't applied, and
+    /// documents taller than 32767 pixels are cropped.
+    pub fn snapshot_texture(&self, width: i32) -> Result<gdk::Texture, cairo::Error> {
+        let imp = self.imp();
+        let surface = crate::export::render_surface(&imp.buffer.borrow(), imp.image_resolver().as_ref(), width)?;
+        crate::export::surface_texture(surface)
+    }
+
+    /// Rasterizes the rendered document like `snapshot_texture`, writing it
+    /// to a PNG file.
+    pub fn save_png(&self, path: impl AsRef<Path>, width: i32) -> Result<(), cairo::IoError> {
+        let imp = self.imp();
+        crate::export::write_png(&imp.buffer.borrow(), imp.image_resolver().as_ref(), width, path)
+    }
+
+    /// Gets the front matter of the rendered document, which is updated on
+    /// every render that changes it.
+    pub fn front_matter(&self) -> Mutable<Option<FrontMatter>> {
+        self.imp().front_matter.clone()
+    }
+
+    /// Adds a transform that is run on the AST before it is rendered.
+    ///
+    /// Transforms run in the order they were added, on every render
+    /// including `append_markdown` and `set_mdast`, and always start from
+    /// the untransformed tree. The current content is rendered again so the
+    /// transform applies right away.
+    pub fn add_transform<F>(&self, transform: F)
+    where
+        F: Fn(&mut markdown::mdast::Node) + 'static,
+    {
+        let imp = self.imp();
+        imp.transforms.borrow_mut().push(Box::new(transform));
+        imp.rerender();
+    }
+
+    /// Removes every transform added with `add_transform`.
+    pub fn clear_transforms(&self) {
+        let imp = self.imp();
+        if !imp.transforms.take().is_empty() {
+            imp.rerender();
+        }
+    }
+
+    /// Appends markdown to the content, e.g. while it is being streamed in.
+    ///
+    /// This is the same as setting `markdown` to the whole new content: the
+    /// document is parsed and flattened again from the start, so streaming a
+    /// document of `n` bytes in small chunks costs O(n²) overall. Only the
+    /// widgets are updated incrementally, blocks whose content didn't change
+    /// are reused as they are. Appending fewer, larger chunks keeps long
+    /// streams cheap. A tree from `set_mdast` is replaced, since it has no
+    /// source to append to.
+    pub fn append_markdown(&self, markdown: &str) {
+        let mut content = if self.imp().shows_mdast.get() {
+            String::new()
+        } else {
+            self.markdown()
+        };
+        content.push_str(markdown);
+        self.set_markdown(content);
+    }
+
+    /// Gets the `TextView` content is rendered into when the render mode is
+    /// `RenderMode::TextView`.
+    pub fn text_view(&self) -> Option<gtk4::TextView> {
+        self.imp().text_view.borrow().as_ref().map(|renderer| renderer.view.clone())
+    }
+
+    /// Returns the widget of the block rendered from the given byte offset
+    /// into the markdown source.
+    ///
+    /// Offsets between blocks resolve to the closest preceding block.
+    pub fn block_at_source_offset(&self, offset: usize) -> Option<gtk4::Widget> {
+        let imp = self.imp();
+        let index = imp.buffer.borrow().block_index_at_source_offset(offset)?;
+        imp.blocks.borrow().get(&index).map(|block| block.root.clone())
+    }
+
+    /// Returns the widget of the block rendered from the given 1-based line
+    /// of the markdown source.
+    ///
+    /// Lines between blocks resolve to the closest preceding block.
+    pub fn block_at_source_line(&self, line: usize) -> Option<gtk4::Widget> {
+        let imp = self.imp();
+        let index = imp.buffer.borrow().block_index_at_source_line(line)?;
+        imp.blocks.borrow().get(&index).map(|block| block.root.clone())
+    }
+
+    /// Returns the byte range of markdown source that the block containing
+    /// `widget` was rendered from.
+    pub fn source_range_for_widget(&self, widget: &impl IsA<gtk4::Widget>) -> Option<Range<usize>> {
+        let index = self.block_index_for_widget(widget.as_ref())?;
+        self.imp().buffer.borrow().blocks.get(index)?.source_range()
+    }
+
+    /// Returns the 1-based range of markdown source lines that the block
+    /// containing `widget` was rendered from, with the end exclusive.
+    pub fn source_lines_for_widget(&self, widget: &impl IsA<gtk4::Widget>) -> Option<Range<usize>> {
+        let index = self.block_index_for_widget(widget.as_ref())?;
+        self.imp().buffer.borrow().blocks.get(index)?.source_lines()
+    }
+
+    fn block_index_for_widget(&self, widget: &gtk4::Widget) -> Option<usize> {
+        self.imp().blocks.borrow().iter()
+            .find(|(_, block)| widget == &block.root || widget.is_ancestor(&block.root))
+            .map(|(i, _)| *i)
+    }
+
+    /// Returns the byte range of markdown source that the block at the given
+    /// point, in the view's coordinate space, was rendered from.
+    pub fn source_range_at(&self, x: f64, y: f64) -> Option<Range<usize>> {
+        let widget = self.pick(x, y, gtk4::PickFlags::DEFAULT)?;
+        self.source_range_for_widget(&widget)
+    }
+
+    /// Highlights every match of `query` across all blocks, returning the
+    /// number of matches.
+    ///
+    /// The highlights follow re-renders until `clear_find` is called.
+    pub fn find(&self, query: &str, options: &FindOptions) -> usize {
+        self.clear_find();
+        if query.is_empty() {
+            return 0;
+        }
+
+        let imp = self.imp();
+        imp.find.replace(Some(imp::FindState {
+            query: query.to_owned(),
+            options: options.clone(),
+            ..Default::default()
+        }));
+
+        imp.refresh_find();
+        self.find_match_count()
+    }
+
+    /// Moves to the next match, wrapping around at the end, and scrolls it
+    /// into view. Returns the index of the new current match.
+    pub fn find_next(&self) -> Option<usize> {
+        self.step_match(true)
+    }
+
+    /// Moves to the previous match, wrapping around at the start, and
+    /// scrolls it into view. Returns the index of the new current match.
+    pub fn find_previous(&self) -> Option<usize> {
+        self.step_match(false)
+    }
+
+    /// Gets the number of matches of the active find.
+    pub fn find_match_count(&self) -> usize {
+        self.imp().find.borrow().as_ref().map_or(0, imp::FindState::total)
+    }
+
+    /// Gets the index of the current match of the active find.
+    pub fn find_current_match(&self) -> Option<usize> {
+        self.imp().find.borrow().as_ref()?.current
+    }
+
+    /// Ends the active find and removes all highlighting.
+    pub fn clear_find(&self) {
+        let imp = self.imp();
+        if imp.find.take().is_some() {
+            for block in imp.blocks.borrow().values() {
+                block.block.clear_find();
+            }
+        }
+    }
+
+    /// Creates a `SearchBar` wired up to find in this view.
+    ///
+    /// Ctrl+F reveals the bar, Enter and the arrow shortcuts of the entry
+    /// move between matches, and Escape hides it again. The bar still has to
+    /// be added to a container by the caller.
+    pub fn create_search_bar(&self) -> gtk4::SearchBar {
+        let entry = gtk4::SearchEntry::builder()
+            .css_classes(["cmark-search-entry"])
+            .hexpand(true)
+            .build();
+
+        let search_bar = gtk4::SearchBar::builder()
+            .css_classes(["cmark-search-bar"])
+            .show_close_button(true)
+            .child(&entry)
+            .build();
+        search_bar.connect_entry(&entry);
+
+        entry.connect_search_changed(glib::clone!(
+            #[weak(rename_to = view)] self,
+            move |entry| {
+                if view.find(&entry.text(), &FindOptions::default()) > 0 {
+                    view.find_next();
+                }
+            }
+        ));
+
+        entry.connect_activate(glib::clone!(
+            #[weak(rename_to = view)] self,
+            move |_| { view.find_next(); }
+        ));
+
+        entry.connect_next_match(glib::clone!(
+            #[weak(rename_to = view)] self,
+            move |_| { view.find_next(); }
+        ));
+
+        entry.connect_previous_match(glib::clone!(
+            #[weak(rename_to = view)] self,
+            move |_| { view.find_previous(); }
+        ));
+
+        search_bar.connect_search_mode_enabled_notify(glib::clone!(
+            #[weak(rename_to = view)] self,
+            move |search_bar| if !search_bar.is_search_mode() {
+                view.clear_find();
+            }
+        ));
+
+        let action = gtk4::CallbackAction::new(glib::clone!(
+            #[weak] search_bar,
+            #[weak] entry,
+            #[upgrade_or] glib::Propagation::Proceed,
+            move |_, _| {
+                search_bar.set_search_mode(true);
+                entry.grab_focus();
+                glib::Propagation::Stop
+            }
+        ));
+
+        let controller = gtk4::ShortcutController::new();
+        controller.set_scope(gtk4::ShortcutScope::Managed);
+        controller.add_shortcut(gtk4::Shortcut::new(
+            gtk4::ShortcutTrigger::parse_string("<Control>f"),
+            Some(action),
+        ));
+        self.add_controller(controller);
+
+        search_bar
+    }
+
+    /// Selects the content of every block.
+    pub fn select_all(&self) {
+        let imp = self.imp();
+        let blocks = imp.sorted_blocks();
+        let (Some((first, _)), Some((last, _))) = (blocks.first(), blocks.last()) else {
+            return;
+        };
+
+        imp.selection.set(Some(imp::ViewSelection {
+            anchor: imp::SelectionPoint { block: *first, offset: 0 },
+            focus: imp::SelectionPoint { block: *last, offset: usize::MAX },
+        }));
+        imp.apply_selection();
+    }
+
+    /// Clears the selection in every block.
+    pub fn unselect_all(&self) {
+        let imp = self.imp();
+        imp.selection.set(None);
+        for (_, block) in imp.sorted_blocks() {
+            block.block.select(None);
+        }
+    }
+
+    /// Gets the plain text of the selection spanning blocks, with blocks
+    /// separated by blank lines.
+    pub fn selected_text(&self) -> Option<String> {
+        let imp = self.imp();
+        let (start, end) = imp.selection.get()?.ordered();
+
+        let text = imp.sorted_blocks().into_iter()
+            .filter(|(i, _)| (start.block..=end.block).contains(i))
+            .map(|(i, block)| {
+                let text = block.block.text();
+                let from = if i == start.block { start.offset.min(text.len()) } else { 0 };
+                let to = if i == end.block { end.offset.min(text.len()) } else { text.len() };
+                text.get(from..to.max(from)).unwrap_or_default().to_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        Some(text)
+    }
+
+    /// Gets the plain text of every block, separated by blank lines like
+    /// `selected_text`.
+    pub(crate) fn blocks_text(&self) -> String {
+        self.imp().sorted_blocks().into_iter()
+            .map(|(_, block)| block.block.text())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Gets the markdown source of the selection spanning blocks.
+    ///
+    /// Partially selected paragraphs and headings are cut where the
+    /// selection starts and ends, widened to whole code spans and the like.
+    /// Other blocks are expanded to the whole block, since their text doesn't
+    /// map back to the source character by character.
+    pub fn selected_markdown(&self) -> Option<String> {
+        let imp = self.imp();
+        if imp.shows_mdast.get() {
+            return None;
+        }
+
+        let (start, end) = imp.selection.get()?.ordered();
+        let buffer = imp.buffer.borrow();
+        let from = buffer.blocks.get(start.block)?.source_range_of(start.offset..usize::MAX)?.start;
+        let to = buffer.blocks.get(end.block)?.source_range_of(0..end.offset)?.end;
+
+        self.markdown().get(from..to).map(str::to_owned)
+    }
+
+    /// Copies the selection spanning blocks to the clipboard, as plain text
+    /// and as its markdown source.
+    pub fn copy_selection(&self) {
+        let Some(text) = self.selected_text() else {
+            return;
+        };
+
+        let mut providers = vec![gdk::ContentProvider::for_value(&text.to_value())];
+        if let Some(markdown) = self.selected_markdown() {
+            let bytes = glib::Bytes::from_owned(markdown.into_bytes());
+            providers.push(gdk::ContentProvider::for_bytes("text/markdown", &bytes));
+        }
+
+        self.clipboard().set_content(Some(&gdk::ContentProvider::new_union(&providers))).ok();
+    }
+
+    /// Renders an already parsed AST, reusing the blocks of the previous
+    /// render the same way setting `markdown` does.
+    ///
+    /// Source positions refer to whatever source the tree was parsed from.
+    /// Until `markdown` is set again, the tree is kept and rendered again
+    /// when options change, the `markdown` property is ignored, and
+    /// `selected_markdown` returns `None`.
+    pub fn set_mdast(&self, mdast: markdown::mdast::Node) {
+        let imp = self.imp();
+        imp.shows_mdast.set(true);
+        imp.render_mdast(mdast);
+    }
+
+    /// Makes the `index`th match of the active find the current one, without scrolling.
+    pub(crate) fn select_match(&self, index: usize) {
+        let imp = self.imp();
+        if let Some(state) = imp.find.borrow_mut().as_mut() {
+            state.current = Some(index).filter(|index| *index < state.total());
+        }
+        imp.select_match(index, false);
+    }
+
+    fn step_match(&self, forward: bool) -> Option<usize> {
+        let imp = self.imp();
+        let current = {
+            let mut find = imp.find.borrow_mut();
+            let state = find.as_mut()?;
+            let total = state.total();
+            if total == 0 {
+                return None;
+            }
+
+            let current = match (state.current, forward) {
+                (None, true) => 0,
+                (None, false) => total - 1,
+                (Some(current), true) => (current + 1) % total,
+                (Some(current), false) => (current + total - 1) % total,
+            };
+
+            state.current = Some(current);
+            current
+        };
+
+        imp.select_match(current, true);
+        Some(current)
+    }
 }
 
 #[derive(Debug, Clone)]
 struct MarkdownBlock {
     root: gtk4::Widget,
     block: Box<dyn BlockWidget>,
+    marker: Option<gtk4::Label>,
 }
 
 impl MarkdownBlock {
     fn new(block: Box<dyn BlockWidget>, marker: Option<&RenderMarker>) -> Self {
-        let root = marker.map_or_else(|| block.root().clone(), |marker| {
-            let indicator = match marker {
-                RenderMarker::Bullet => "•",
-                RenderMarker::Ordered(index) => &format!("{}.", index),
-            };
+        let marker_label = marker.map(|marker| gtk4::Label::builder()
+            .css_classes(["marker-label"])
+            .valign(gtk4::Align::Start)
+            .label(marker.label())
+            .build());
 
-            let marker_label = gtk4::Label::builder()
-                .css_classes(["marker-label"])
-                .valign(gtk4::Align::Start)
-                .label(indicator)
-                .build();
+        let root = marker_label.as_ref().map_or_else(|| block.root().clone(), |marker_label| {
 
             let marker_box = gtk4::Box::builder()
                 .orientation(gtk4::Orientation::Horizontal)
@@ -58,7 +623,7 @@ impl MarkdownBlock {
                 .css_classes(["marker-box"])
                 .build();
 
-            marker_box.append(&marker_label);
+            marker_box.append(marker_label);
             marker_box.append(block.root());
             marker_box.upcast()
         });
@@ -66,6 +631,35 @@ impl MarkdownBlock {
         Self {
             root,
             block,
+            marker: marker_label,
         }
     }
-}
\ No newline at end of file
+
+    /// Returns true if the block can show `marker`, which needs the block to
+    /// have a marker exactly when `marker` is set, since that changes its root.
+    fn fits_marker(&self, marker: Option<&RenderMarker>) -> bool {
+        self.marker.is_some() == marker.is_some()
+    }
+
+    /// Updates the marker shown before the block, e.g. when a task is checked.
+    fn set_marker(&self, marker: Option<&RenderMarker>) {
+        if let (Some(label), Some(marker)) = (&self.marker, marker) {
+            label.set_label(&marker.label());
+        }
+    }
+}
+
+/// Emits `link-activated` on the views a widget is in, from the innermost
+/// out, and opens the link with the default handler unless one stopped it.
+pub(crate) fn activate_link(widget: &impl IsA<gtk4::Widget>, uri: &str) {
+    let mut ancestor = widget.ancestor(MarkdownView::static_type());
+    while let Some(view) = ancestor.and_downcast::<MarkdownView>() {
+        if view.emit_by_name::<bool>("link-activated", &[&uri]) {
+            return;
+        }
+        ancestor = view.parent().and_then(|parent| parent.ancestor(MarkdownView::static_type()));
+    }
+
+    let window = widget.root().and_downcast::<gtk4::Window>();
+    gtk4::UriLauncher::new(uri).launch(window.as_ref(), gio::Cancellable::NONE, |_| {});
+}